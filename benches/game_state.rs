@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use networking_basic::common::{GameStateUpdate, PlayerState, ServerMessage, Timestamp, Vector2};
+use networking_basic::server::{GameRules, GameState};
+use std::time::Duration;
+
+const PLAYER_COUNTS: [usize; 3] = [10, 100, 1000];
+
+// Lays players out on a fixed grid with alternating velocities so every run
+// sees the same positions and collisions
+fn build_state(player_count: usize) -> GameState {
+    let rules = GameRules {
+        rng_seed: Some(42),
+        ..Default::default()
+    };
+    let (min, max) = rules.map_bounds;
+    let columns = (player_count as f32).sqrt().ceil() as usize;
+    let spacing = (max - min) / columns as f32;
+
+    let mut state = GameState::with_rules(rules);
+    for i in 0..player_count {
+        let player = PlayerState {
+            position: Vector2 {
+                x: min + spacing * (i % columns) as f32,
+                y: min + spacing * (i / columns) as f32,
+            },
+            velocity: Vector2 {
+                x: if i % 2 == 0 { 5.0 } else { -5.0 },
+                y: if i % 3 == 0 { 5.0 } else { -5.0 },
+            },
+            ..Default::default()
+        };
+        state.add_player(format!("player{}", i), player);
+    }
+    state
+}
+
+fn bench_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("game_state_update");
+    for player_count in PLAYER_COUNTS {
+        let state = build_state(player_count);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(player_count),
+            &state,
+            |b, state| {
+                b.iter_batched(
+                    || state.clone(),
+                    |mut state| state.update(Duration::from_millis(16)).unwrap(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_serialize_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize_snapshot");
+    for player_count in PLAYER_COUNTS {
+        let state = build_state(player_count);
+        let message = ServerMessage::GameState(GameStateUpdate::new(
+            1,
+            state.players().clone(),
+            Timestamp::default(),
+        ));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(player_count),
+            &message,
+            |b, message| b.iter(|| serde_json::to_vec(message).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_update, bench_serialize_snapshot);
+criterion_main!(benches);
@@ -1,7 +1,7 @@
 use clap::Parser;
 use networking_basic::{
     client::GameClient,
-    common::{ClientMessage, Result, Vector2},
+    common::{Result, Timestamp, Vector2},
     logging,
 };
 use std::{thread, time::Duration};
@@ -14,6 +14,45 @@ struct Opts {
 
     #[clap(short, long)]
     debug: bool,
+
+    // Show interpolated positions next to the raw snapshot ones in the debug output
+    #[clap(short, long)]
+    interpolate: bool,
+
+    // Fixed interpolation delay; the client's adaptive delay is used when omitted
+    #[clap(long)]
+    interp_delay_ms: Option<u64>,
+}
+
+// Raw and interpolated position of every player in the latest snapshot
+fn interpolation_report(client: &GameClient, delay: Duration) -> String {
+    let Some(update) = client.last_update() else {
+        return "No snapshot yet\n".to_string();
+    };
+    let render_time = Timestamp::from_millis(
+        update
+            .server_time
+            .as_millis()
+            .saturating_sub(delay.as_millis() as u64),
+    );
+
+    let mut ids: Vec<&String> = update.players.keys().collect();
+    ids.sort();
+    let mut report = format!("Interpolating {:?} behind\n", delay);
+    for id in ids {
+        let raw = update.players[id].position;
+        match client.interpolated_player(id, &render_time) {
+            Some(smooth) => report.push_str(&format!(
+                "  {} raw ({:.2}, {:.2}) interpolated ({:.2}, {:.2})\n",
+                id, raw.x, raw.y, smooth.position.x, smooth.position.y
+            )),
+            None => report.push_str(&format!(
+                "  {} raw ({:.2}, {:.2}) interpolated -\n",
+                id, raw.x, raw.y
+            )),
+        }
+    }
+    report
 }
 
 fn main() -> Result<()> {
@@ -61,6 +100,13 @@ fn main() -> Result<()> {
             print!("\x1B[2J\x1B[1;1H");
             println!("=== Debug Information ===");
             println!("{}", client.debug_info());
+            if opts.interpolate {
+                let delay = opts
+                    .interp_delay_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or_else(|| client.interpolation_delay());
+                println!("{}", interpolation_report(&client, delay));
+            }
             println!("========================");
 
             last_debug = std::time::Instant::now();
@@ -81,3 +127,21 @@ fn main() -> Result<()> {
         thread::sleep(Duration::from_millis(16));
     }
 }
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolation_flags_parsed() {
+        let opts = Opts::try_parse_from(["client", "--interpolate", "--interp-delay-ms", "120"])
+            .expect("Flags should parse");
+        assert!(opts.interpolate);
+        assert_eq!(opts.interp_delay_ms, Some(120));
+
+        let opts = Opts::try_parse_from(["client"]).unwrap();
+        assert!(!opts.interpolate);
+        assert_eq!(opts.interp_delay_ms, None);
+    }
+}
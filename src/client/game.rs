@@ -1,23 +1,298 @@
-use crate::common::{ClientMessage, GameError, PlayerState, Result, ServerMessage, Vector2};
+use super::jitter::JitterBuffer;
+use super::quality::{ConnectionQuality, QualityInputs};
+use super::recording::{read_recording, RecordedMessage, Recorder};
+use super::view::ViewTransform;
+use crate::common::{
+    validate_name, ClientMessage, ErrorCode, GameError, GameStateDelta, GameStateUpdate,
+    PlayerState, Result, ServerMessage, Timestamp, Vector2, WorldInfo,
+};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     io::{BufRead, BufReader, Write},
-    net::TcpStream,
-    sync::mpsc::{self, Receiver, Sender},
+    net::{Shutdown, SocketAddr, TcpStream},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SendError, Sender, TryRecvError},
+        Arc, Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+type SharedRecorder = Arc<Mutex<Option<Recorder>>>;
+
+// Round trip times and clock offset measured from Ping/Pong exchanges
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencyStats {
+    pub last_rtt: Option<Duration>,
+    pub smoothed_rtt: Option<Duration>,
+    // Server minus client clock in ms, from the latest Pong
+    pub clock_offset_ms: Option<i64>,
+}
+
+impl LatencyStats {
+    // Assumes the Pong's server_time was taken halfway through the round trip
+    fn record_pong(
+        &mut self,
+        sent_at: &Timestamp,
+        server_time: &Timestamp,
+        received_at: &Timestamp,
+    ) {
+        let midpoint = (sent_at.as_millis() + received_at.as_millis()) / 2;
+        self.clock_offset_ms = Some(server_time.as_millis() as i64 - midpoint as i64);
+        self.record(received_at.duration_since(sent_at));
+    }
+
+    fn record(&mut self, rtt: Duration) {
+        self.last_rtt = Some(rtt);
+        self.smoothed_rtt = Some(match self.smoothed_rtt {
+            Some(smoothed) => smoothed.mul_f32(0.875) + rtt.mul_f32(0.125),
+            None => rtt,
+        });
+    }
+}
+
+// Lifecycle of the client's connection to the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    // Socket open, not yet accepted into the game
+    Connecting,
+    // JoinAccepted received, waiting for the first snapshot
+    Joined,
+    // Receiving game state
+    Active,
+    // Replacing a dropped connection
+    Reconnecting,
+    Disconnected,
+}
+
+// Something the application may want to show, queued for poll_events
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientEvent {
+    // An Error the server sent, as opposed to a local failure returned as GameError
+    ServerError {
+        error_code: ErrorCode,
+        message: String,
+    },
+}
+
+// Called with every server message the client processes, see on_message
+pub type MessageCallback = Box<dyn FnMut(&ServerMessage) + Send>;
+
+// Events kept for poll_events; the oldest go first if the app never polls
+const MAX_QUEUED_EVENTS: usize = 64;
+
+// Complete snapshots kept for interpolating remote players
+const SNAPSHOT_HISTORY: usize = 32;
+
+// Pings sent to stay inside a server read deadline, for servers that set one
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Parts of a split snapshot received so far
+struct PartialSnapshot {
+    update: GameStateUpdate,
+    parts_received: HashSet<u32>,
+}
+
 pub struct GameClient {
-    stream: TcpStream,
+    address: String,
+    // None for offline clients built by replay
+    stream: Option<TcpStream>,
+    // Both ends of the current connection, kept so they survive the socket closing
+    server_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
     server_message_receiver: Receiver<ServerMessage>,
     game_command_sender: Sender<ClientMessage>,
     player_id: Option<String>,
+    connection_state: ConnectionState,
+    // Name sent with the last join, reused when rejoining after reconnect
+    join_name: Option<String>,
+    // Name the server gave this player, which its NamePolicy may have changed
+    own_name: Option<String>,
+    // Identity token sent with every join, see set_identity_token
+    join_token: Option<String>,
+    // Reliable messages awaiting an Ack, retransmitted on reconnect
+    unacked: BTreeMap<u64, ClientMessage>,
+    next_reliable_seq: u64,
     current_state: Option<HashMap<String, PlayerState>>,
+    last_update: Option<GameStateUpdate>,
+    // Recent complete snapshots, oldest first
+    snapshots: VecDeque<GameStateUpdate>,
+    partial_snapshot: Option<PartialSnapshot>,
+    latency: Arc<Mutex<LatencyStats>>,
+    ping_loop_running: Option<Arc<AtomicBool>>,
+    // Kept on the client rather than in its threads so both outlive a reconnect
+    ping_interval: Option<Duration>,
+    message_callbacks: Vec<MessageCallback>,
+    events: VecDeque<ClientEvent>,
+    init_data: Option<Vec<u8>>,
+    world_info: Option<WorldInfo>,
+    // Slot to player id, for decoding SlotSnapshot
+    slots: HashMap<u32, String>,
+    jitter: JitterBuffer,
+    // Arena corners from the latest snapshot that carried them
+    snapshot_bounds: Option<(Vector2, Vector2)>,
+    // Tick of the latest snapshot, and how many were skipped over between snapshots
+    last_seen_tick: Option<u64>,
+    missed_snapshots: u64,
+    // Smoothed fraction of snapshots missed, updated per snapshot received
+    snapshot_gap_rate: f64,
+    view: ViewTransform,
+    // Shared with the writer thread, which records what it sends
+    recorder: SharedRecorder,
+    writer_thread: Option<thread::JoinHandle<()>>,
+    // Shared with the writer thread, see set_move_coalescing
+    coalesce_moves: Arc<AtomicBool>,
+    // Set by the first close, so teardown only ever happens once
+    closed: bool,
 }
 
+// Sockets and threads for one connection: stream, parsed messages in, commands out
+type Connection = (
+    TcpStream,
+    Receiver<ServerMessage>,
+    Sender<ClientMessage>,
+    thread::JoinHandle<()>,
+);
+
 impl GameClient {
     pub fn new(address: &str) -> Result<Self> {
+        let latency = Arc::new(Mutex::new(LatencyStats::default()));
+        let coalesce_moves = Arc::new(AtomicBool::new(false));
+        let recorder = SharedRecorder::default();
+        let (stream, server_message_receiver, game_command_sender, writer_thread) =
+            Self::open_connection(
+                address,
+                latency.clone(),
+                coalesce_moves.clone(),
+                recorder.clone(),
+            )?;
+
+        let mut client = Self::from_parts(
+            address,
+            Some(stream),
+            server_message_receiver,
+            game_command_sender,
+            latency,
+        );
+        client.writer_thread = Some(writer_thread);
+        client.coalesce_moves = coalesce_moves;
+        client.recorder = recorder;
+        // Silent clients are dropped at the server's read deadline, so ping from the start.
+        // The first waits an interval, since a fresh connection isn't silent yet
+        client.start_pings(KEEPALIVE_INTERVAL, KEEPALIVE_INTERVAL);
+        Ok(client)
+    }
+
+    // Rebuilds client state offline by processing the server messages of a recording
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let (_, server_message_receiver) = mpsc::channel();
+        let (game_command_sender, _) = mpsc::channel();
+        let latency = Arc::new(Mutex::new(LatencyStats::default()));
+        let mut client = Self::from_parts(
+            "",
+            None,
+            server_message_receiver,
+            game_command_sender,
+            latency,
+        );
+        client.connection_state = ConnectionState::Disconnected;
+
+        let entries = read_recording(path)?;
+        let Some(last) = entries.iter().rev().find_map(|entry| match entry {
+            RecordedMessage::Received { at, .. } => Some(at.clone()),
+            _ => None,
+        }) else {
+            return Ok(client);
+        };
+        // Keep the recorded spacing between arrivals, ending at now
+        let now = Instant::now();
+        for entry in entries {
+            if let RecordedMessage::Received { at, message } = entry {
+                let received = now.checked_sub(last.duration_since(&at)).unwrap_or(now);
+                client.process_server_message_at(message, received);
+            }
+        }
+        Ok(client)
+    }
+
+    fn from_parts(
+        address: &str,
+        stream: Option<TcpStream>,
+        server_message_receiver: Receiver<ServerMessage>,
+        game_command_sender: Sender<ClientMessage>,
+        latency: Arc<Mutex<LatencyStats>>,
+    ) -> Self {
+        Self {
+            address: address.to_string(),
+            server_addr: stream.as_ref().and_then(|stream| stream.peer_addr().ok()),
+            local_addr: stream.as_ref().and_then(|stream| stream.local_addr().ok()),
+            stream,
+            server_message_receiver,
+            game_command_sender,
+            player_id: None,
+            connection_state: ConnectionState::Connecting,
+            join_name: None,
+            own_name: None,
+            join_token: None,
+            unacked: BTreeMap::new(),
+            next_reliable_seq: 0,
+            current_state: None,
+            last_update: None,
+            snapshots: VecDeque::new(),
+            partial_snapshot: None,
+            latency,
+            ping_loop_running: None,
+            ping_interval: None,
+            message_callbacks: Vec::new(),
+            events: VecDeque::new(),
+            init_data: None,
+            world_info: None,
+            slots: HashMap::new(),
+            jitter: JitterBuffer::default(),
+            snapshot_bounds: None,
+            last_seen_tick: None,
+            missed_snapshots: 0,
+            snapshot_gap_rate: 0.0,
+            view: ViewTransform::default(),
+            recorder: SharedRecorder::default(),
+            writer_thread: None,
+            coalesce_moves: Arc::new(AtomicBool::new(false)),
+            closed: false,
+        }
+    }
+
+    // Logs every sent and processed message to `path` until stop_recording
+    pub fn record_to(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let recorder = Recorder::create(path)?;
+        if let Ok(mut shared) = self.recorder.lock() {
+            *shared = Some(recorder);
+        }
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Ok(mut shared) = self.recorder.lock() {
+            *shared = None;
+        }
+    }
+
+    // Sent messages are recorded by the writer thread, as it writes them
+    fn send_command(
+        &mut self,
+        message: ClientMessage,
+    ) -> std::result::Result<(), SendError<ClientMessage>> {
+        self.game_command_sender.send(message)
+    }
+
+    // Connects and spawns the reader and writer threads for one connection
+    fn open_connection(
+        address: &str,
+        latency: Arc<Mutex<LatencyStats>>,
+        coalesce_moves: Arc<AtomicBool>,
+        recorder: SharedRecorder,
+    ) -> Result<Connection> {
         let stream = TcpStream::connect(address)?;
         stream.set_nonblocking(true)?;
 
@@ -30,29 +305,38 @@ impl GameClient {
         // Spawn reader thread
         let reader_stream = stream.try_clone()?;
         thread::spawn(move || {
-            if let Err(e) = Self::handle_server_messages(reader_stream, server_msg_sender) {
+            if let Err(e) = Self::handle_server_messages(reader_stream, server_msg_sender, latency)
+            {
                 eprintln!("Server message handler error: {}", e);
             }
         });
 
         // Spawn writer thread
         let writer_stream = stream.try_clone()?;
-        thread::spawn(move || {
-            if let Err(e) = Self::handle_client_messages(writer_stream, game_command_receiver) {
+        let writer_thread = thread::spawn(move || {
+            if let Err(e) = Self::handle_client_messages(
+                writer_stream,
+                game_command_receiver,
+                &coalesce_moves,
+                &recorder,
+            ) {
                 eprintln!("Client message handler error: {}", e);
             }
         });
 
-        Ok(Self {
+        Ok((
             stream,
             server_message_receiver,
             game_command_sender,
-            player_id: None,
-            current_state: None,
-        })
+            writer_thread,
+        ))
     }
 
-    fn handle_server_messages(stream: TcpStream, sender: Sender<ServerMessage>) -> Result<()> {
+    fn handle_server_messages(
+        stream: TcpStream,
+        sender: Sender<ServerMessage>,
+        latency: Arc<Mutex<LatencyStats>>,
+    ) -> Result<()> {
         let mut reader = BufReader::new(stream);
         let mut line = String::new();
 
@@ -67,6 +351,16 @@ impl GameClient {
                     match serde_json::from_str::<ServerMessage>(&line) {
                         Ok(msg) => {
                             println!("Parsed server message: {:?}", msg);
+                            // Measure on arrival so the RTT excludes time queued for update()
+                            if let ServerMessage::Pong {
+                                sent_at,
+                                server_time,
+                            } = &msg
+                            {
+                                if let Ok(mut stats) = latency.lock() {
+                                    stats.record_pong(sent_at, server_time, &Timestamp::now());
+                                }
+                            }
                             if sender.send(msg).is_err() {
                                 break;
                             }
@@ -85,27 +379,63 @@ impl GameClient {
     }
 
     fn handle_client_messages(
-        mut stream: TcpStream,
+        mut stream: impl Write,
         receiver: Receiver<ClientMessage>,
+        coalesce_moves: &AtomicBool,
+        recorder: &Mutex<Option<Recorder>>,
     ) -> Result<()> {
-        loop {
-            match receiver.recv() {
-                Ok(msg) => {
-                    let json = serde_json::to_string(&msg)?;
-                    stream.write_all(format!("{}\n", json).as_bytes())?;
-                    stream.flush()?;
+        while let Ok(first) = receiver.recv() {
+            // Everything queued while the last write was in progress
+            let mut pending: Vec<ClientMessage> =
+                std::iter::once(first).chain(receiver.try_iter()).collect();
+            if coalesce_moves.load(Ordering::SeqCst) {
+                pending = latest_move_only(pending);
+            }
+            for msg in pending {
+                // Recorded here so keepalive pings are too, and coalesced moves aren't
+                record(recorder, || RecordedMessage::Sent {
+                    at: Timestamp::now(),
+                    message: msg.clone(),
+                });
+                let json = serde_json::to_string(&msg)?;
+                stream.write_all(format!("{}\n", json).as_bytes())?;
+                stream.flush()?;
+                // Nothing is sent after a Disconnect, so close() can wait for it to flush
+                if matches!(msg, ClientMessage::Disconnect) {
+                    return Ok(());
                 }
-                Err(_) => break,
             }
         }
         Ok(())
     }
 
+    // When on, a lagging writer sends only the newest queued Move, since earlier
+    // directions are already stale. Other messages are always sent, in order
+    pub fn set_move_coalescing(&mut self, enabled: bool) {
+        self.coalesce_moves.store(enabled, Ordering::SeqCst);
+    }
+
+    // Connects and joins in one call, returning once the server accepts the join
+    pub fn connect_new(address: &str, name: &str) -> Result<Self> {
+        validate_name(name)?;
+        let mut client = Self::new(address)?;
+        client.join(Some(name.to_string()))?;
+        Ok(client)
+    }
+
     pub fn connect(&mut self) -> Result<()> {
+        self.join(None)
+    }
+
+    fn join(&mut self, name: Option<String>) -> Result<()> {
+        self.join_name = name.clone();
+
         // Send join message
-        self.game_command_sender
-            .send(ClientMessage::Join)
-            .map_err(|_| GameError::NetworkError("Failed to send join message".into()))?;
+        self.send_command(ClientMessage::Join {
+            name,
+            token: self.join_token.clone(),
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send join message".into()))?;
 
         // Wait for join acceptance
         let timeout = Duration::from_secs(5);
@@ -114,14 +444,27 @@ impl GameClient {
         while start.elapsed() < timeout {
             if let Ok(msg) = self.server_message_receiver.try_recv() {
                 match msg {
-                    ServerMessage::JoinAccepted { player_id } => {
-                        self.player_id = Some(player_id);
+                    ServerMessage::JoinAccepted { .. } => {
+                        self.process_server_message(msg);
                         return Ok(());
                     }
-                    ServerMessage::Error { message } => {
-                        return Err(GameError::NetworkError(message));
+                    // Recorded and seen by callbacks, but it fails the join
+                    // rather than queueing a ServerError event
+                    ServerMessage::Error {
+                        error_code,
+                        message,
+                    } => {
+                        let error = GameError::ServerError {
+                            code: error_code,
+                            message: message.clone(),
+                        };
+                        self.observe(&ServerMessage::Error {
+                            error_code,
+                            message,
+                        });
+                        return Err(error);
                     }
-                    _ => continue,
+                    other => self.process_server_message(other),
                 }
             }
             thread::sleep(Duration::from_millis(100));
@@ -130,43 +473,681 @@ impl GameClient {
         Err(GameError::NetworkError("Connection timeout".into()))
     }
 
+    // Watches the game without joining; full snapshots arrive at the server's spectator rate
+    pub fn spectate(&mut self) -> Result<()> {
+        self.send_command(ClientMessage::Spectate)
+            .map_err(|_| GameError::NetworkError("Failed to send spectate".into()))?;
+        Ok(())
+    }
+
     pub fn move_player(&mut self, direction: Vector2) -> Result<()> {
-        self.game_command_sender
-            .send(ClientMessage::Move { direction })
-            .map_err(|_| GameError::NetworkError("Failed to send move command".into()))?;
+        self.send_command(ClientMessage::Move {
+            direction,
+            client_time: Some(Timestamp::now().as_millis()),
+            throttle: None,
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send move command".into()))?;
         Ok(())
     }
 
-    pub fn update(&mut self) -> Result<()> {
-        // Process any pending server messages
-        while let Ok(msg) = self.server_message_receiver.try_recv() {
-            match msg {
-                ServerMessage::GameState(update) => {
-                    self.current_state = Some(update.players);
+    // Only honored for players the server has made client-authoritative
+    pub fn report_position(&mut self, position: Vector2) -> Result<()> {
+        self.send_command(ClientMessage::ReportPosition { position })
+            .map_err(|_| GameError::NetworkError("Failed to send position report".into()))?;
+        Ok(())
+    }
+
+    // Moves toward `direction` at `throttle` (0 to 1) of the server's max speed,
+    // e.g. 0.5 to walk and 1.0 to run. Only the direction's heading matters
+    pub fn steer(&mut self, direction: Vector2, throttle: f32) -> Result<()> {
+        self.send_command(ClientMessage::Move {
+            direction,
+            client_time: Some(Timestamp::now().as_millis()),
+            throttle: Some(throttle),
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send move command".into()))?;
+        Ok(())
+    }
+
+    // Sends several messages as one frame, e.g. inputs buffered since the last frame.
+    // The server handles them in order
+    pub fn send_batch(&mut self, messages: Vec<ClientMessage>) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        self.send_command(ClientMessage::Batch { messages })
+            .map_err(|_| GameError::NetworkError("Failed to send batch".into()))?;
+        Ok(())
+    }
+
+    // Sends a message the server must acknowledge; it is retransmitted after
+    // reconnect until acked, so the server may see it more than once
+    pub fn send_reliable(&mut self, message: ClientMessage) -> Result<u64> {
+        let seq = self.next_reliable_seq;
+        self.next_reliable_seq += 1;
+        self.unacked.insert(seq, message.clone());
+        self.send_reliable_seq(seq, message)?;
+        Ok(seq)
+    }
+
+    fn send_reliable_seq(&mut self, seq: u64, message: ClientMessage) -> Result<()> {
+        self.send_command(ClientMessage::Reliable {
+            seq,
+            message: Box::new(message),
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send reliable message".into()))
+    }
+
+    pub fn unacked_count(&self) -> usize {
+        self.unacked.len()
+    }
+
+    // Identifies this client across connections; joining again with the same token
+    // replaces or is refused by the old connection, per the server's DuplicatePolicy
+    pub fn set_identity_token(&mut self, token: &str) {
+        self.join_token = Some(token.to_string());
+    }
+
+    // Opens a fresh connection to the same server, rejoins if previously joined,
+    // then retransmits unacknowledged reliable messages in order
+    pub fn reconnect(&mut self) -> Result<()> {
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.connection_state = ConnectionState::Reconnecting;
+
+        let (stream, server_message_receiver, game_command_sender, writer_thread) =
+            match Self::open_connection(
+                &self.address,
+                self.latency.clone(),
+                self.coalesce_moves.clone(),
+                self.recorder.clone(),
+            ) {
+                Ok(connection) => connection,
+                Err(e) => {
+                    self.connection_state = ConnectionState::Disconnected;
+                    return Err(e);
                 }
-                ServerMessage::Error { message } => {
-                    eprintln!("Server error: {}", message);
+            };
+        self.connection_state = ConnectionState::Connecting;
+        self.server_addr = stream.peer_addr().ok();
+        self.local_addr = stream.local_addr().ok();
+        self.stream = Some(stream);
+        self.server_message_receiver = server_message_receiver;
+        self.game_command_sender = game_command_sender;
+        self.writer_thread = Some(writer_thread);
+        self.closed = false;
+        self.partial_snapshot = None;
+
+        // The old ping thread holds the old connection's sender
+        if let Some(interval) = self.ping_interval {
+            self.ping_loop(interval);
+        }
+
+        if self.player_id.take().is_some() {
+            self.join(self.join_name.clone())?;
+        }
+
+        let pending: Vec<(u64, ClientMessage)> = self
+            .unacked
+            .iter()
+            .map(|(seq, message)| (*seq, message.clone()))
+            .collect();
+        for (seq, message) in pending {
+            self.send_reliable_seq(seq, message)?;
+        }
+        Ok(())
+    }
+
+    // Requests a new display name; the server broadcasts it once accepted
+    pub fn set_name(&mut self, name: &str) -> Result<()> {
+        validate_name(name)?;
+        self.send_command(ClientMessage::SetName {
+            name: name.to_string(),
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send rename".into()))?;
+        Ok(())
+    }
+
+    // Chat is relayed to everyone as ServerMessage::ChatMessage, see poll_nonblocking
+    pub fn send_chat(&mut self, text: &str) -> Result<()> {
+        self.send_command(ClientMessage::Chat {
+            text: text.to_string(),
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send chat".into()))?;
+        Ok(())
+    }
+
+    pub fn send_ping(&mut self) -> Result<()> {
+        self.send_command(ClientMessage::Ping {
+            sent_at: Timestamp::now(),
+        })
+        .map_err(|_| GameError::NetworkError("Failed to send ping".into()))?;
+        Ok(())
+    }
+
+    // Sends a Ping and blocks until its Pong arrives, returning the round trip time.
+    // Other messages received meanwhile are processed as in update()
+    pub fn ping(&mut self, timeout: Duration) -> Result<Duration> {
+        let sent_at = Timestamp::now();
+        let sent_millis = sent_at.as_millis();
+        let start = std::time::Instant::now();
+        self.send_command(ClientMessage::Ping { sent_at })
+            .map_err(|_| GameError::NetworkError("Failed to send ping".into()))?;
+
+        self.wait_for(
+            |msg| {
+                matches!(
+                    msg,
+                    ServerMessage::Pong { sent_at, .. } if sent_at.as_millis() == sent_millis
+                )
+            },
+            timeout,
+        )?;
+        Ok(start.elapsed())
+    }
+
+    // Blocks until a message matching `pred` arrives and returns it. Messages received
+    // before it are applied to the client state as in update()
+    pub fn wait_for<F: Fn(&ServerMessage) -> bool>(
+        &mut self,
+        pred: F,
+        timeout: Duration,
+    ) -> Result<ServerMessage> {
+        let start = std::time::Instant::now();
+        loop {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            match self.server_message_receiver.recv_timeout(remaining) {
+                Ok(msg) => {
+                    self.process_server_message(msg.clone());
+                    if pred(&msg) {
+                        return Ok(msg);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(GameError::NetworkError(format!(
+                        "No matching message within {:?}",
+                        timeout
+                    )));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.connection_state = ConnectionState::Disconnected;
+                    return Err(GameError::NetworkError("Connection closed".into()));
                 }
-                _ => {}
             }
         }
+    }
+
+    // Sends a Ping every `interval` from a background thread until disconnect
+    pub fn ping_loop(&mut self, interval: Duration) {
+        self.start_pings(interval, Duration::ZERO);
+    }
+
+    fn start_pings(&mut self, interval: Duration, first_delay: Duration) {
+        self.stop_ping_loop();
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let sender = self.game_command_sender.clone();
+        thread::spawn(move || {
+            thread::sleep(first_delay);
+            while thread_running.load(Ordering::Relaxed) {
+                let ping = ClientMessage::Ping {
+                    sent_at: Timestamp::now(),
+                };
+                if sender.send(ping).is_err() {
+                    break;
+                }
+                thread::sleep(interval);
+            }
+        });
+
+        self.ping_loop_running = Some(running);
+        self.ping_interval = Some(interval);
+    }
+
+    pub fn stop_ping_loop(&mut self) {
+        if let Some(running) = self.ping_loop_running.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+        self.ping_interval = None;
+    }
+
+    // Registers a callback run for every processed server message, in arrival order.
+    // Callbacks stay registered across reconnects
+    pub fn on_message(&mut self, callback: impl FnMut(&ServerMessage) + Send + 'static) {
+        self.message_callbacks.push(Box::new(callback));
+    }
+
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.latency.lock().map(|stats| *stats).unwrap_or_default()
+    }
+
+    // The server this client is (or was last) connected to; None for replays
+    pub fn server_addr(&self) -> Option<SocketAddr> {
+        self.server_addr
+    }
+
+    // This end of the connection, e.g. to tell several clients' connections apart
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    pub fn last_rtt(&self) -> Option<Duration> {
+        self.latency_stats().last_rtt
+    }
+
+    // Server minus client clock in ms (negative when the server is behind), assuming
+    // symmetric latency. None until a Pong has arrived
+    pub fn clock_offset(&self) -> Option<i64> {
+        self.latency_stats().clock_offset_ms
+    }
+
+    // The server's clock right now, by the measured offset
+    pub fn estimated_server_time(&self) -> Option<Timestamp> {
+        let offset = self.clock_offset()?;
+        let now = Timestamp::now().as_millis() as i64;
+        Some(Timestamp::from_millis((now + offset).max(0) as u64))
+    }
+
+    pub fn update(&mut self) -> Result<()> {
+        self.poll_nonblocking();
         Ok(())
     }
 
+    // Applies every queued server message like update(), and also returns them in
+    // arrival order for callers that want to handle messages themselves
+    pub fn poll_nonblocking(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        loop {
+            match self.server_message_receiver.try_recv() {
+                Ok(msg) => {
+                    self.process_server_message(msg.clone());
+                    messages.push(msg);
+                }
+                Err(TryRecvError::Empty) => break,
+                // Reader thread exited: the server closed the connection
+                Err(TryRecvError::Disconnected) => {
+                    self.connection_state = ConnectionState::Disconnected;
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    // Processes queued server messages like update(), then returns the events they
+    // raised, oldest first
+    pub fn poll_events(&mut self) -> Vec<ClientEvent> {
+        self.poll_nonblocking();
+        self.events.drain(..).collect()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.connection_state
+    }
+
+    fn process_server_message(&mut self, msg: ServerMessage) {
+        self.process_server_message_at(msg, Instant::now());
+    }
+
+    // `received` is when the message arrived, which replays take from the recording
+    fn process_server_message_at(&mut self, msg: ServerMessage, received: Instant) {
+        self.observe(&msg);
+
+        match msg {
+            ServerMessage::JoinAccepted { player_id, name } => {
+                self.player_id = Some(player_id);
+                self.own_name = name;
+                self.connection_state = ConnectionState::Joined;
+            }
+            ServerMessage::GameState(update) => {
+                self.apply_update(update, received);
+            }
+            ServerMessage::GameStateDelta(delta) => {
+                self.apply_delta(delta, received);
+            }
+            ServerMessage::NoChange { tick } => {
+                // Idle ticks without snapshots aren't missed ones; restart counting
+                self.last_seen_tick = Some(tick);
+                self.jitter.record_keepalive(received);
+            }
+            ServerMessage::SlotAssigned { slot, player_id } => {
+                self.slots.insert(slot, player_id);
+            }
+            ServerMessage::SlotReleased { slot } => {
+                self.slots.remove(&slot);
+            }
+            ServerMessage::SlotSnapshot(snapshot) => {
+                let update = snapshot.resolve(&self.slots);
+                self.apply_update(update, received);
+            }
+            ServerMessage::Ack { seq } => {
+                self.unacked.remove(&seq);
+            }
+            ServerMessage::WorldInfo(world_info) => {
+                self.world_info = Some(world_info);
+            }
+            ServerMessage::InitData { payload } => {
+                self.init_data = Some(payload);
+            }
+            ServerMessage::PlayerRenamed { player_id, name } => {
+                if self.is_own(&player_id) {
+                    self.own_name = Some(name.clone());
+                }
+                if let Some(player) = self
+                    .current_state
+                    .as_mut()
+                    .and_then(|state| state.get_mut(&player_id))
+                {
+                    player.name = Some(name);
+                }
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                if let Some(state) = self.current_state.as_mut() {
+                    state.remove(&player_id);
+                }
+                // Later deltas are based on the snapshot without this player
+                if let Some(update) = self.last_update.as_mut() {
+                    update.players.remove(&player_id);
+                }
+            }
+            ServerMessage::Error {
+                error_code,
+                message,
+            } => {
+                eprintln!("Server error ({:?}): {}", error_code, message);
+                if self.events.len() == MAX_QUEUED_EVENTS {
+                    self.events.pop_front();
+                }
+                self.events.push_back(ClientEvent::ServerError {
+                    error_code,
+                    message,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Records the message and passes it to callbacks
+    fn observe(&mut self, msg: &ServerMessage) {
+        record(&self.recorder, || RecordedMessage::Received {
+            at: Timestamp::now(),
+            message: msg.clone(),
+        });
+        for callback in self.message_callbacks.iter_mut() {
+            callback(msg);
+        }
+    }
+
+    fn apply_update(&mut self, update: GameStateUpdate, received: Instant) {
+        if update.is_complete() {
+            if self.connection_state == ConnectionState::Joined {
+                self.connection_state = ConnectionState::Active;
+            }
+            self.note_tick(update.tick);
+            self.current_state = Some(update.players.clone());
+            if update.bounds.is_some() {
+                self.snapshot_bounds = update.bounds;
+            }
+            self.jitter.record_arrival(received);
+            if self.snapshots.len() == SNAPSHOT_HISTORY {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back(update.clone());
+            self.last_update = Some(update);
+            return;
+        }
+
+        if let Some(complete) = self.assemble_part(update) {
+            self.apply_update(complete, received);
+        }
+    }
+
+    // Counts snapshots skipped since the last one, allowing for feeds (e.g. spectators)
+    // that the server broadcasts less often than every tick
+    fn note_tick(&mut self, tick: u64) {
+        let stride = self
+            .world_info
+            .map(|info| (info.broadcast_rate_ms / info.tick_rate_ms.max(1)).max(1))
+            .unwrap_or(1);
+        if let Some(last) = self.last_seen_tick {
+            if tick <= last {
+                return;
+            }
+            let missed = ((tick - last) / stride).saturating_sub(1);
+            if missed > 0 {
+                println!("Missed {} snapshot(s) before tick {}", missed, tick);
+                self.missed_snapshots += missed;
+            }
+            let lost = missed as f64 / (missed + 1) as f64;
+            self.snapshot_gap_rate += (lost - self.snapshot_gap_rate) / 16.0;
+        }
+        self.last_seen_tick = Some(tick);
+    }
+
+    // Snapshots that never arrived, judged by gaps in their ticks
+    pub fn missed_snapshots(&self) -> u64 {
+        self.missed_snapshots
+    }
+
+    // RTT, jitter, snapshot gaps and staleness rolled into one rating. Lost once
+    // disconnected or when snapshots stop arriving
+    pub fn connection_quality(&self) -> ConnectionQuality {
+        if matches!(
+            self.connection_state,
+            ConnectionState::Disconnected | ConnectionState::Reconnecting
+        ) {
+            return ConnectionQuality::Lost;
+        }
+        ConnectionQuality::classify(&self.quality_inputs())
+    }
+
+    fn quality_inputs(&self) -> QualityInputs {
+        QualityInputs {
+            rtt: self.latency_stats().smoothed_rtt,
+            jitter: self.jitter.jitter(),
+            gap_rate: self.snapshot_gap_rate,
+            staleness: self.jitter.last_arrival().map(|at| at.elapsed()),
+            expected_interval: self.server_broadcast_rate(),
+        }
+    }
+
+    // Deltas only apply on top of the snapshot they were computed from
+    fn apply_delta(&mut self, delta: GameStateDelta, received: Instant) {
+        let base_tick = self.last_update.as_ref().map(|update| update.tick);
+        let (Some(mut players), Some(base_tick)) = (self.current_state.clone(), base_tick) else {
+            return;
+        };
+        if base_tick != delta.base_tick {
+            eprintln!(
+                "Dropping delta for tick {}: have tick {}, need {}",
+                delta.tick, base_tick, delta.base_tick
+            );
+            return;
+        }
+
+        delta.apply_to(&mut players);
+        self.apply_update(
+            GameStateUpdate::new(delta.tick, players, delta.server_time),
+            received,
+        );
+    }
+
+    // Buffers one part of a split snapshot, returning the whole snapshot once all arrive
+    fn assemble_part(&mut self, update: GameStateUpdate) -> Option<GameStateUpdate> {
+        let tick = update.tick;
+        let pending_tick = self.partial_snapshot.as_ref().map(|p| p.update.tick);
+
+        match pending_tick {
+            Some(pending) if tick < pending => return None, // Stale part
+            Some(pending) if tick == pending => {
+                let partial = self.partial_snapshot.as_mut()?;
+                if partial.parts_received.insert(update.part) {
+                    partial.update.players.extend(update.players);
+                }
+            }
+            // A newer tick abandons any incomplete older snapshot
+            _ => {
+                self.partial_snapshot = Some(PartialSnapshot {
+                    parts_received: HashSet::from([update.part]),
+                    update,
+                });
+            }
+        }
+
+        let partial = self.partial_snapshot.as_ref()?;
+        if partial.parts_received.len() < partial.update.of as usize {
+            return None;
+        }
+
+        let mut complete = self.partial_snapshot.take()?.update;
+        complete.part = 0;
+        complete.of = 1;
+        Some(complete)
+    }
+
+    // Map bounds the server simulates with, known once joined
+    pub fn world_bounds(&self) -> Option<(f32, f32)> {
+        self.world_info.map(|info| info.map_bounds)
+    }
+
+    // Arena (min, max) corners, from a snapshot or else WorldInfo
+    pub fn arena_bounds(&self) -> Option<(Vector2, Vector2)> {
+        self.snapshot_bounds.or_else(|| {
+            self.world_bounds()
+                .map(|(min, max)| (Vector2 { x: min, y: min }, Vector2 { x: max, y: max }))
+        })
+    }
+
+    pub fn world_info(&self) -> Option<WorldInfo> {
+        self.world_info
+    }
+
+    // How often the server sends snapshots, known once joined
+    pub fn server_broadcast_rate(&self) -> Option<Duration> {
+        self.world_info
+            .map(|info| Duration::from_millis(info.broadcast_rate_ms))
+    }
+
+    // Blob the server sent on join, if it has one configured
+    pub fn init_data(&self) -> Option<&[u8]> {
+        self.init_data.as_deref()
+    }
+
+    // Latest snapshot as received, including its tick and server time
+    pub fn last_update(&self) -> Option<&GameStateUpdate> {
+        self.last_update.as_ref()
+    }
+
     pub fn get_player_state(&self, player_id: &str) -> Option<&PlayerState> {
         self.current_state.as_ref()?.get(player_id)
     }
 
+    // Client-side only: the server keeps simulating in world coordinates
+    pub fn set_view_transform(&mut self, offset: Vector2, scale: f32, flip_y: bool) {
+        self.view = ViewTransform {
+            offset,
+            scale,
+            flip_y,
+        };
+    }
+
+    // A world position in the client's view coordinates
+    pub fn to_view(&self, position: Vector2) -> Vector2 {
+        self.view.apply(position)
+    }
+
+    pub fn view_position(&self, player_id: &str) -> Option<Vector2> {
+        self.get_player_state(player_id)
+            .map(|player| self.to_view(player.position))
+    }
+
+    // Every player in the current state, including our own
+    pub fn all_players(&self) -> impl Iterator<Item = (&String, &PlayerState)> {
+        self.current_state.iter().flat_map(|state| state.iter())
+    }
+
+    // State of any player at `render_time` (server clock), interpolated between the
+    // two snapshots around it. None if the player is missing from either snapshot
+    // or render_time is outside the buffered history.
+    pub fn interpolated_player(&self, id: &str, render_time: &Timestamp) -> Option<PlayerState> {
+        let render = render_time.as_millis();
+        let (from, to) = self
+            .snapshots
+            .iter()
+            .zip(self.snapshots.iter().skip(1))
+            .find(|(from, to)| {
+                from.server_time.as_millis() <= render && render <= to.server_time.as_millis()
+            })?;
+
+        let start = from.players.get(id)?;
+        let end = to.players.get(id)?;
+        let span = to
+            .server_time
+            .duration_since(&from.server_time)
+            .as_secs_f32();
+        let t = if span > 0.0 {
+            render_time.duration_since(&from.server_time).as_secs_f32() / span
+        } else {
+            1.0
+        };
+
+        Some(PlayerState {
+            position: start.position.lerp(&end.position, t),
+            velocity: start.velocity.lerp(&end.velocity, t),
+            ..end.clone()
+        })
+    }
+
+    // How far behind the newest snapshot to render, adapted to snapshot arrival jitter
+    pub fn interpolation_delay(&self) -> Duration {
+        self.jitter.delay()
+    }
+
     pub fn get_own_state(&self) -> Option<&PlayerState> {
-        self.player_id
-            .as_ref()
-            .and_then(|id| self.get_player_state(id))
+        self.own_id().and_then(|id| self.get_player_state(id))
+    }
+
+    // Id assigned by the server on join
+    pub fn own_id(&self) -> Option<&str> {
+        self.player_id.as_deref()
+    }
+
+    // Name the server accepted, e.g. "Alice 2" when another Alice was already playing
+    pub fn own_name(&self) -> Option<&str> {
+        self.own_name.as_deref()
+    }
+
+    pub fn is_own(&self, id: &str) -> bool {
+        self.own_id() == Some(id)
     }
 
     pub fn disconnect(&mut self) -> Result<()> {
-        self.game_command_sender
-            .send(ClientMessage::Disconnect)
-            .map_err(|_| GameError::NetworkError("Failed to send disconnect message".into()))?;
+        self.close()
+    }
+
+    // Tears the connection down once: sends Disconnect, waits for the writer thread to
+    // flush it, then closes the socket. Later calls, and dropping the client, do nothing
+    pub fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.stop_ping_loop();
+        self.connection_state = ConnectionState::Disconnected;
+
+        // Fails only if the writer already exited with the connection, which needs no
+        // Disconnect; drop runs this too, so it stays quiet
+        let _ = self.send_command(ClientMessage::Disconnect);
+        if let Some(writer) = self.writer_thread.take() {
+            if writer.join().is_err() {
+                eprintln!("Client writer thread panicked");
+            }
+        }
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
         Ok(())
     }
 
@@ -174,13 +1155,15 @@ impl GameClient {
         let mut info = String::new();
 
         // Connection status
+        info.push_str(&format!("Connection: {:?}\n", self.connection_state));
         info.push_str(&format!(
-            "Connection: {}\n",
-            if self.player_id.is_some() {
-                "Connected"
-            } else {
-                "Disconnected"
-            }
+            "Interpolation delay: {:?}\n",
+            self.interpolation_delay()
+        ));
+        info.push_str(&format!("Missed snapshots: {}\n", self.missed_snapshots));
+        info.push_str(&format!(
+            "Connection quality: {:?}\n",
+            self.connection_quality()
         ));
 
         // Player info
@@ -201,15 +1184,14 @@ impl GameClient {
 
         // Other players
         if let Some(state) = &self.current_state {
-            info.push_str(&format!("Other players: {}\n", state.len() - 1));
+            let others: Vec<_> = state.iter().filter(|(id, _)| !self.is_own(id)).collect();
+            info.push_str(&format!("Other players: {}\n", others.len()));
 
-            for (id, player) in state {
-                if Some(id) != self.player_id.as_ref() {
-                    info.push_str(&format!(
-                        "  {} at ({:.2}, {:.2})\n",
-                        id, player.position.x, player.position.y
-                    ));
-                }
+            for (id, player) in others {
+                info.push_str(&format!(
+                    "  {} at ({:.2}, {:.2})\n",
+                    id, player.position.x, player.position.y
+                ));
             }
         }
 
@@ -217,32 +1199,916 @@ impl GameClient {
     }
 }
 
-// Example usage
-fn main() -> Result<()> {
-    let mut client = GameClient::new("127.0.0.1:8080")?;
+impl Drop for GameClient {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+// Writes the entry if recording, building it only then. A failed write stops the recording
+fn record(recorder: &Mutex<Option<Recorder>>, entry: impl FnOnce() -> RecordedMessage) {
+    let Ok(mut shared) = recorder.lock() else {
+        return;
+    };
+    if let Some(active) = shared.as_mut() {
+        if let Err(e) = active.record(&entry()) {
+            eprintln!("Failed to record message, recording stopped: {}", e);
+            *shared = None;
+        }
+    }
+}
 
-    // Connect and join game
-    client.connect()?;
+// Drops every Move except the last, keeping other messages where they were
+fn latest_move_only(messages: Vec<ClientMessage>) -> Vec<ClientMessage> {
+    let Some(last_move) = messages
+        .iter()
+        .rposition(|msg| matches!(msg, ClientMessage::Move { .. }))
+    else {
+        return messages;
+    };
+    messages
+        .into_iter()
+        .enumerate()
+        .filter(|(i, msg)| *i == last_move || !matches!(msg, ClientMessage::Move { .. }))
+        .map(|(_, msg)| msg)
+        .collect()
+}
 
-    println!("Connected to server!");
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::math::{approx_eq_f32, EPSILON};
+    use crate::common::{PlayerDelta, SlotSnapshot};
+    use std::net::TcpListener;
 
-    // Game loop
-    let mut last_print = std::time::Instant::now();
-    loop {
-        // Send movement
-        client.move_player(Vector2 { x: 1.0, y: 0.0 })?;
+    // Minimal server that accepts joins and answers pings
+    fn spawn_test_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let address = listener.local_addr().unwrap().to_string();
 
-        // Update game state
-        client.update()?;
+        // Serves each connection the same way, so clients can reconnect
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                thread::spawn(move || {
+                    let mut writer = stream.try_clone().unwrap();
+                    let reader = BufReader::new(stream);
 
-        // Print state every second
-        if last_print.elapsed() >= Duration::from_secs(1) {
-            if let Some(state) = client.get_own_state() {
-                println!("Current position: {:?}", state.position);
+                    for line in reader.lines() {
+                        let Ok(line) = line else { break };
+                        let reply = match serde_json::from_str::<ClientMessage>(&line) {
+                            Ok(ClientMessage::Join { .. }) => ServerMessage::JoinAccepted {
+                                player_id: "test_player".to_string(),
+                                name: None,
+                            },
+                            Ok(ClientMessage::Ping { sent_at }) => ServerMessage::Pong {
+                                sent_at,
+                                server_time: Timestamp::now(),
+                            },
+                            _ => continue,
+                        };
+                        let json = serde_json::to_string(&reply).unwrap();
+                        if writer.write_all(format!("{}\n", json).as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                });
             }
-            last_print = std::time::Instant::now();
+        });
+
+        address
+    }
+
+    #[test]
+    fn test_callbacks_and_ping_loop_survive_reconnect() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        let pongs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = pongs.clone();
+        client.on_message(move |msg| {
+            if matches!(msg, ServerMessage::Pong { .. }) {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        client.ping_loop(Duration::from_millis(20));
+
+        client.reconnect().expect("Reconnect failed");
+
+        // Only the restarted ping loop can produce a Pong on the new connection
+        client
+            .wait_for(
+                |msg| matches!(msg, ServerMessage::Pong { .. }),
+                Duration::from_secs(2),
+            )
+            .expect("Ping loop should resume after reconnect");
+        assert!(pongs.load(Ordering::SeqCst) >= 1);
+        client.stop_ping_loop();
+    }
+
+    #[test]
+    fn test_reliable_message_retransmitted_after_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let address = listener.local_addr().unwrap().to_string();
+        let (received_sender, received) = mpsc::channel();
+
+        // Drops the first connection without acking, acks on the second
+        thread::spawn(move || {
+            for connection in 0..2 {
+                let (stream, _) = listener.accept().expect("Failed to accept");
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                    if let Ok(ClientMessage::Reliable { seq, message }) =
+                        serde_json::from_str::<ClientMessage>(&line)
+                    {
+                        received_sender.send((connection, *message)).unwrap();
+                        if connection == 0 {
+                            break;
+                        }
+                        let ack = serde_json::to_string(&ServerMessage::Ack { seq }).unwrap();
+                        writer.write_all(format!("{}\n", ack).as_bytes()).unwrap();
+                    }
+                    line.clear();
+                }
+            }
+        });
+
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client
+            .send_reliable(ClientMessage::SetName {
+                name: "Alice".to_string(),
+            })
+            .expect("Send failed");
+
+        let (connection, _) = received.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(connection, 0);
+        assert_eq!(client.unacked_count(), 1);
+
+        client.reconnect().expect("Reconnect failed");
+
+        let (connection, message) = received.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(connection, 1);
+        assert!(matches!(message, ClientMessage::SetName { name } if name == "Alice"));
+
+        let start = std::time::Instant::now();
+        while client.unacked_count() > 0 && start.elapsed() < Duration::from_secs(2) {
+            client.update().unwrap();
+            thread::sleep(Duration::from_millis(10));
         }
+        assert_eq!(client.unacked_count(), 0, "Ack should clear the message");
+    }
+
+    #[test]
+    fn test_connect_new_joins() {
+        let address = spawn_test_server();
+        let client = GameClient::connect_new(&address, "Alice").expect("Failed to join");
+
+        assert_eq!(client.player_id.as_deref(), Some("test_player"));
+    }
+
+    #[test]
+    fn test_connection_state_lifecycle() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        assert_eq!(client.state(), ConnectionState::Connecting);
+
+        client.connect().expect("Failed to join");
+        assert_eq!(client.state(), ConnectionState::Joined);
+
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            1,
+            HashMap::new(),
+            Timestamp::now(),
+        )));
+        assert_eq!(client.state(), ConnectionState::Active);
+        assert!(client.debug_info().contains("Connection: Active"));
+
+        client.disconnect().expect("Disconnect failed");
+        assert_eq!(client.state(), ConnectionState::Disconnected);
+    }
+
+    #[test]
+    fn test_is_own_matches_joined_id() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        assert!(!client.is_own("test_player"));
+
+        client.connect().expect("Failed to join");
+        assert_eq!(client.own_id(), Some("test_player"));
+        assert!(client.is_own("test_player"));
+        assert!(!client.is_own("other_player"));
+    }
+
+    #[test]
+    fn test_own_name_follows_server() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client.process_server_message(ServerMessage::JoinAccepted {
+            player_id: "p1".to_string(),
+            name: Some("Alice 2".to_string()),
+        });
+        assert_eq!(client.own_name(), Some("Alice 2"));
+
+        client.process_server_message(ServerMessage::PlayerRenamed {
+            player_id: "p2".to_string(),
+            name: "Bob".to_string(),
+        });
+        assert_eq!(client.own_name(), Some("Alice 2"));
+        client.process_server_message(ServerMessage::PlayerRenamed {
+            player_id: "p1".to_string(),
+            name: "Alicia".to_string(),
+        });
+        assert_eq!(client.own_name(), Some("Alicia"));
+    }
+
+    #[test]
+    fn test_poll_nonblocking_returns_queued_messages_in_order() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        for millis in 1..=3 {
+            client
+                .game_command_sender
+                .send(ClientMessage::Ping {
+                    sent_at: Timestamp::from_millis(millis),
+                })
+                .unwrap();
+        }
+
+        let mut messages = Vec::new();
+        let start = std::time::Instant::now();
+        while messages.len() < 3 && start.elapsed() < Duration::from_secs(2) {
+            messages.extend(client.poll_nonblocking());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(messages.len(), 3);
+        let sent: Vec<u64> = messages
+            .into_iter()
+            .map(|message| match message {
+                ServerMessage::Pong { sent_at, .. } => sent_at.as_millis(),
+                other => panic!("Expected Pong, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(sent, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_recording_replays_to_same_state() {
+        let path =
+            std::env::temp_dir().join(format!("client-recording-{}.jsonl", std::process::id()));
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client.record_to(&path).expect("Failed to start recording");
+
+        client.connect().expect("Failed to join");
+        let mut players = HashMap::new();
+        players.insert(
+            "test_player".to_string(),
+            PlayerState {
+                position: Vector2 { x: 4.0, y: -2.0 },
+                ..Default::default()
+            },
+        );
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            5,
+            players,
+            Timestamp::now(),
+        )));
+        client.stop_recording();
+
+        let entries = read_recording(&path).expect("Failed to read recording");
+        assert!(matches!(
+            entries[0],
+            RecordedMessage::Sent {
+                message: ClientMessage::Join { .. },
+                ..
+            }
+        ));
+
+        let replayed = GameClient::replay(&path).expect("Replay failed");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(replayed.own_id(), Some("test_player"));
+        assert_eq!(
+            serde_json::to_value(&replayed.current_state).unwrap(),
+            serde_json::to_value(&client.current_state).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_recording_includes_keepalive_pings() {
+        let path = std::env::temp_dir().join(format!("client-pings-{}.jsonl", std::process::id()));
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client.record_to(&path).expect("Failed to start recording");
+        client.ping_loop(Duration::from_millis(20));
+        thread::sleep(Duration::from_millis(150));
+        client.stop_ping_loop();
+        client.stop_recording();
+
+        let entries = read_recording(&path).expect("Failed to read recording");
+        std::fs::remove_file(&path).ok();
+        assert!(entries.iter().any(|entry| matches!(
+            entry,
+            RecordedMessage::Sent {
+                message: ClientMessage::Ping { .. },
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn test_replay_keeps_recorded_arrival_spacing() {
+        let path =
+            std::env::temp_dir().join(format!("client-spacing-{}.jsonl", std::process::id()));
+        let mut recorder = Recorder::create(&path).expect("Failed to create recording");
+        for tick in 0..5 {
+            recorder
+                .record(&RecordedMessage::Received {
+                    at: Timestamp::from_millis(1_000 + tick * 200),
+                    message: ServerMessage::GameState(GameStateUpdate::new(
+                        tick,
+                        HashMap::new(),
+                        Timestamp::now(),
+                    )),
+                })
+                .unwrap();
+        }
+        drop(recorder);
+
+        let replayed = GameClient::replay(&path).expect("Replay failed");
+        std::fs::remove_file(&path).ok();
+
+        // Processed in an instant, but buffered for snapshots 200ms apart
+        assert!(replayed.jitter.delay() >= Duration::from_millis(400));
+        assert!(replayed.jitter.last_arrival().unwrap().elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_join_error_recorded_and_seen_by_callbacks() {
+        let path =
+            std::env::temp_dir().join(format!("client-join-error-{}.jsonl", std::process::id()));
+        let (server_sender, server_message_receiver) = mpsc::channel();
+        let (game_command_sender, _commands) = mpsc::channel();
+        let mut client = GameClient::from_parts(
+            "",
+            None,
+            server_message_receiver,
+            game_command_sender,
+            Arc::new(Mutex::new(LatencyStats::default())),
+        );
+        client.record_to(&path).expect("Failed to start recording");
+        let errors = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = errors.clone();
+        client.on_message(move |msg| {
+            if matches!(msg, ServerMessage::Error { .. }) {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        server_sender
+            .send(ServerMessage::Error {
+                error_code: ErrorCode::ServerFull,
+                message: "Server is full".to_string(),
+            })
+            .unwrap();
+        assert!(matches!(
+            client.connect(),
+            Err(GameError::ServerError {
+                code: ErrorCode::ServerFull,
+                ..
+            })
+        ));
+        client.stop_recording();
+
+        let entries = read_recording(&path).expect("Failed to read recording");
+        std::fs::remove_file(&path).ok();
+        assert!(entries.iter().any(|entry| matches!(
+            entry,
+            RecordedMessage::Received {
+                message: ServerMessage::Error { .. },
+                ..
+            }
+        )));
+        assert_eq!(errors.load(Ordering::SeqCst), 1);
+        // The failed join is the caller's error, not a queued event
+        assert!(client.events.is_empty());
+    }
+
+    #[test]
+    fn test_init_data_stored() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        assert!(client.init_data().is_none());
+
+        client.process_server_message(ServerMessage::InitData {
+            payload: b"level-1".to_vec(),
+        });
+        assert_eq!(client.init_data(), Some(&b"level-1"[..]));
+    }
+
+    #[test]
+    fn test_ping_loop_populates_rtt() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        assert!(client.last_rtt().is_none());
+
+        client.ping_loop(Duration::from_millis(20));
+
+        let start = std::time::Instant::now();
+        while client.last_rtt().is_none() && start.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(client.last_rtt().is_some(), "Ping loop should measure RTT");
+        client.disconnect().expect("Disconnect failed");
+    }
+
+    #[test]
+    fn test_new_client_keeps_alive_until_stopped() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        assert_eq!(client.ping_interval, Some(KEEPALIVE_INTERVAL));
+        client.spectate().expect("Spectate failed");
+        assert_eq!(client.ping_interval, Some(KEEPALIVE_INTERVAL));
+
+        client.stop_ping_loop();
+        assert!(client.ping_interval.is_none());
+        client.reconnect().expect("Reconnect failed");
+        assert!(
+            client.ping_interval.is_none(),
+            "Opting out survives reconnect"
+        );
+    }
+
+    #[test]
+    fn test_disconnect_is_idempotent() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let address = listener.local_addr().unwrap().to_string();
+        let (count_sender, disconnects) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("Failed to accept");
+            let count = BufReader::new(stream)
+                .lines()
+                .map_while(|line| line.ok())
+                .filter(|line| matches!(serde_json::from_str(line), Ok(ClientMessage::Disconnect)))
+                .count();
+            let _ = count_sender.send(count);
+        });
+
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client.disconnect().expect("First disconnect failed");
+        client.disconnect().expect("Second disconnect failed");
+        client.close().expect("Close after disconnect failed");
+        drop(client);
+
+        let sent = disconnects
+            .recv_timeout(Duration::from_secs(2))
+            .expect("Server should see the connection close");
+        assert_eq!(sent, 1, "Disconnect should be sent exactly once");
+    }
+
+    #[test]
+    fn test_clock_offset_from_pong_timestamps() {
+        let mut stats = LatencyStats::default();
+
+        // 100ms round trip; the server stamped 4000ms past the client's midpoint
+        stats.record_pong(
+            &Timestamp::from_millis(1_000),
+            &Timestamp::from_millis(5_050),
+            &Timestamp::from_millis(1_100),
+        );
+        assert_eq!(stats.clock_offset_ms, Some(4_000));
+        assert_eq!(stats.last_rtt, Some(Duration::from_millis(100)));
+
+        // A server clock behind the client gives a negative offset
+        stats.record_pong(
+            &Timestamp::from_millis(10_000),
+            &Timestamp::from_millis(7_020),
+            &Timestamp::from_millis(10_040),
+        );
+        assert_eq!(stats.clock_offset_ms, Some(-3_000));
+    }
+
+    #[test]
+    fn test_coalescing_sends_only_latest_move() {
+        let (sender, receiver) = mpsc::channel();
+        for x in 1..=5 {
+            sender
+                .send(ClientMessage::Move {
+                    direction: Vector2 {
+                        x: x as f32,
+                        y: 0.0,
+                    },
+                    client_time: None,
+                    throttle: None,
+                })
+                .unwrap();
+            if x == 2 {
+                sender
+                    .send(ClientMessage::Chat { text: "hi".into() })
+                    .unwrap();
+            }
+        }
+        sender.send(ClientMessage::Disconnect).unwrap();
+
+        let mut written = Vec::new();
+        GameClient::handle_client_messages(
+            &mut written,
+            receiver,
+            &AtomicBool::new(true),
+            &Mutex::new(None),
+        )
+        .unwrap();
+
+        let sent: Vec<ClientMessage> = String::from_utf8(written)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                ClientMessage::Chat { text: "hi".into() },
+                ClientMessage::Move {
+                    direction: Vector2 { x: 5.0, y: 0.0 },
+                    client_time: None,
+                    throttle: None,
+                },
+                ClientMessage::Disconnect,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_connection_addresses() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).unwrap();
+
+        assert_eq!(client.server_addr().unwrap().to_string(), address);
+        let local = client.local_addr().unwrap();
+        assert!(local.ip().is_loopback());
+        assert_ne!(local, client.server_addr().unwrap());
+
+        // Still known after the socket is gone
+        client.close().unwrap();
+        assert_eq!(client.server_addr().unwrap().to_string(), address);
+    }
+
+    #[test]
+    fn test_blocking_ping_returns_rtt() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+
+        let rtt = client.ping(Duration::from_secs(2)).expect("Ping failed");
+        assert!(rtt < Duration::from_secs(1), "Local RTT was {:?}", rtt);
+        client.disconnect().expect("Disconnect failed");
+    }
+
+    #[test]
+    fn test_wait_for_skips_and_applies_other_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let address = listener.local_addr().unwrap().to_string();
+
+        // Answers each ping with a few snapshots before the pong
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("Failed to accept");
+            let mut writer = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                let Ok(ClientMessage::Ping { sent_at }) = serde_json::from_str(&line) else {
+                    continue;
+                };
+                let mut replies: Vec<ServerMessage> = (1..=3)
+                    .map(|tick| {
+                        ServerMessage::GameState(GameStateUpdate::new(
+                            tick,
+                            HashMap::new(),
+                            Timestamp::now(),
+                        ))
+                    })
+                    .collect();
+                replies.push(ServerMessage::Pong {
+                    sent_at,
+                    server_time: Timestamp::now(),
+                });
+                for reply in replies {
+                    let json = serde_json::to_string(&reply).unwrap();
+                    writer.write_all(format!("{}\n", json).as_bytes()).unwrap();
+                }
+            }
+        });
+
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client.send_ping().expect("Ping failed");
+        let message = client
+            .wait_for(
+                |msg| matches!(msg, ServerMessage::Pong { .. }),
+                Duration::from_secs(2),
+            )
+            .expect("No pong");
+
+        assert!(matches!(message, ServerMessage::Pong { .. }));
+        assert_eq!(client.last_update().map(|update| update.tick), Some(3));
+        assert!(client
+            .wait_for(|_| true, Duration::from_millis(50))
+            .is_err());
+    }
+
+    #[test]
+    fn test_last_update_keeps_tick_and_server_time() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        assert!(client.last_update().is_none());
+
+        let server_time = Timestamp::now();
+        let mut players = HashMap::new();
+        players.insert("test_player".to_string(), PlayerState::default());
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            42,
+            players,
+            server_time.clone(),
+        )));
+
+        let update = client.last_update().expect("Snapshot not stored");
+        assert_eq!(update.tick, 42);
+        assert_eq!(
+            update.server_time.duration_since(&server_time),
+            Duration::ZERO
+        );
+        assert_eq!(
+            server_time.duration_since(&update.server_time),
+            Duration::ZERO
+        );
+        assert!(client.get_player_state("test_player").is_some());
+    }
+
+    #[test]
+    fn test_interpolated_remote_player() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+
+        let snapshot = |tick: u64, millis: u64, players: &[(&str, f32)]| {
+            let players = players
+                .iter()
+                .map(|(id, x)| {
+                    let state = PlayerState {
+                        position: Vector2 { x: *x, y: 0.0 },
+                        ..Default::default()
+                    };
+                    (id.to_string(), state)
+                })
+                .collect();
+            ServerMessage::GameState(GameStateUpdate::new(
+                tick,
+                players,
+                Timestamp::from_millis(millis),
+            ))
+        };
+
+        client.process_server_message(snapshot(1, 1000, &[("remote", 0.0)]));
+        client.process_server_message(snapshot(2, 1100, &[("remote", 10.0), ("new", 5.0)]));
+
+        let state = client
+            .interpolated_player("remote", &Timestamp::from_millis(1025))
+            .expect("Remote player should interpolate");
+        assert!(approx_eq_f32(state.position.x, 2.5, EPSILON));
+
+        // Not enough history: only in the second snapshot, or outside the buffer
+        assert!(client
+            .interpolated_player("new", &Timestamp::from_millis(1050))
+            .is_none());
+        assert!(client
+            .interpolated_player("remote", &Timestamp::from_millis(1200))
+            .is_none());
+    }
+
+    #[test]
+    fn test_view_transform_flips_y() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        let player = PlayerState {
+            position: Vector2 { x: 0.0, y: 10.0 },
+            ..Default::default()
+        };
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            1,
+            HashMap::from([("remote".to_string(), player)]),
+            Timestamp::now(),
+        )));
+
+        client.set_view_transform(Vector2 { x: 0.0, y: 0.0 }, 1.0, true);
+        let position = client.view_position("remote").unwrap();
+        assert_eq!((position.x, position.y), (0.0, -10.0));
+
+        // Raw state is untouched
+        assert_eq!(client.get_player_state("remote").unwrap().position.y, 10.0);
+
+        // Top-left origin: scale then offset
+        client.set_view_transform(Vector2 { x: 400.0, y: 300.0 }, 2.0, true);
+        let position = client.view_position("remote").unwrap();
+        assert_eq!((position.x, position.y), (400.0, 280.0));
+    }
+
+    #[test]
+    fn test_server_error_surfaces_as_event() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        client.process_server_message(ServerMessage::Error {
+            error_code: ErrorCode::RateLimited,
+            message: "Slow down".to_string(),
+        });
+
+        assert_eq!(
+            client.poll_events(),
+            vec![ClientEvent::ServerError {
+                error_code: ErrorCode::RateLimited,
+                message: "Slow down".to_string(),
+            }]
+        );
+        assert!(client.poll_events().is_empty(), "Events are drained once");
+    }
+
+    #[test]
+    fn test_snapshot_gap_detected() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        for tick in [1, 2, 5] {
+            client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+                tick,
+                HashMap::new(),
+                Timestamp::now(),
+            )));
+        }
+
+        assert_eq!(client.missed_snapshots(), 2);
+        assert!(client.debug_info().contains("Missed snapshots: 2"));
+
+        // A stale snapshot isn't a gap
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            4,
+            HashMap::new(),
+            Timestamp::now(),
+        )));
+        assert_eq!(client.missed_snapshots(), 2);
+    }
+
+    #[test]
+    fn test_keepalives_and_slow_feeds_are_not_stale() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        let ago = |millis| std::time::Instant::now() - Duration::from_millis(millis);
+
+        client.jitter.record_arrival(ago(5000));
+        assert_eq!(client.connection_quality(), ConnectionQuality::Lost);
+        client.process_server_message(ServerMessage::NoChange { tick: 3 });
+        assert_eq!(client.connection_quality(), ConnectionQuality::Good);
+
+        // Half a second between spectator snapshots is on schedule, not Poor
+        client.jitter.record_keepalive(ago(1500));
+        assert_eq!(client.connection_quality(), ConnectionQuality::Poor);
+        client.process_server_message(ServerMessage::WorldInfo(WorldInfo {
+            map_bounds: (-100.0, 100.0),
+            max_velocity: 10.0,
+            tick_rate_ms: 16,
+            broadcast_rate_ms: 500,
+        }));
+        assert_eq!(client.connection_quality(), ConnectionQuality::Good);
+    }
+
+    #[test]
+    fn test_player_left_prunes_state() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        let players = HashMap::from([
+            ("stays".to_string(), PlayerState::default()),
+            ("leaves".to_string(), PlayerState::default()),
+        ]);
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            1,
+            players,
+            Timestamp::now(),
+        )));
+
+        client.process_server_message(ServerMessage::PlayerLeft {
+            player_id: "leaves".to_string(),
+        });
+
+        let ids: Vec<&String> = client.all_players().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec!["stays"]);
+        assert!(!client.last_update().unwrap().players.contains_key("leaves"));
+    }
+
+    #[test]
+    fn test_delta_applied_on_matching_base() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        let mut players = HashMap::new();
+        players.insert("remote".to_string(), PlayerState::default());
+        client.process_server_message(ServerMessage::GameState(GameStateUpdate::new(
+            1,
+            players,
+            Timestamp::now(),
+        )));
+
+        let delta = |base_tick: u64, tick: u64, x: f32| {
+            ServerMessage::GameStateDelta(GameStateDelta {
+                tick,
+                base_tick,
+                server_time: Timestamp::now(),
+                changed: HashMap::from([(
+                    "remote".to_string(),
+                    PlayerDelta {
+                        position: Some(Vector2 { x, y: 0.0 }),
+                        ..Default::default()
+                    },
+                )]),
+                removed: Vec::new(),
+            })
+        };
+
+        client.process_server_message(delta(1, 2, 3.0));
+        assert_eq!(client.last_update().unwrap().tick, 2);
+        assert_eq!(client.get_player_state("remote").unwrap().position.x, 3.0);
+
+        // A delta against a snapshot we never saw is ignored
+        client.process_server_message(delta(5, 6, 9.0));
+        assert_eq!(client.last_update().unwrap().tick, 2);
+        assert_eq!(client.get_player_state("remote").unwrap().position.x, 3.0);
+    }
+
+    #[test]
+    fn test_slot_snapshot_decoded_with_mapping() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+        let snapshot = |tick, slots: &[u32]| {
+            ServerMessage::SlotSnapshot(SlotSnapshot {
+                tick,
+                players: slots
+                    .iter()
+                    .map(|&slot| (slot, PlayerState::default()))
+                    .collect(),
+                server_time: Timestamp::default(),
+                part: 0,
+                of: 1,
+                bounds: None,
+            })
+        };
+
+        for (slot, id) in [(0, "a"), (1, "b")] {
+            client.process_server_message(ServerMessage::SlotAssigned {
+                slot,
+                player_id: id.to_string(),
+            });
+        }
+        client.process_server_message(snapshot(1, &[0, 1]));
+        assert!(client.get_player_state("b").is_some());
+
+        // b leaves and c takes its slot
+        client.process_server_message(ServerMessage::SlotReleased { slot: 1 });
+        client.process_server_message(ServerMessage::SlotAssigned {
+            slot: 1,
+            player_id: "c".to_string(),
+        });
+        client.process_server_message(snapshot(2, &[0, 1]));
+
+        assert!(client.get_player_state("a").is_some());
+        assert!(client.get_player_state("b").is_none());
+        assert!(client.get_player_state("c").is_some());
+    }
+
+    #[test]
+    fn test_split_snapshot_reassembled() {
+        let address = spawn_test_server();
+        let mut client = GameClient::new(&address).expect("Failed to connect");
+
+        let part = |tick: u64, part: u32, ids: &[&str]| {
+            let players = ids
+                .iter()
+                .map(|id| (id.to_string(), PlayerState::default()))
+                .collect();
+            ServerMessage::GameState(GameStateUpdate {
+                part,
+                of: 3,
+                ..GameStateUpdate::new(tick, players, Timestamp::now())
+            })
+        };
+
+        // An incomplete older tick is abandoned when a newer one starts
+        client.process_server_message(part(1, 0, &["stale"]));
+        client.process_server_message(part(2, 1, &["p3", "p4"]));
+        client.process_server_message(part(2, 0, &["p1", "p2"]));
+        assert!(client.last_update().is_none(), "Snapshot not complete yet");
+
+        client.process_server_message(part(2, 2, &["p5"]));
 
-        thread::sleep(Duration::from_millis(16));
+        let update = client.last_update().expect("Snapshot should be complete");
+        assert_eq!(update.tick, 2);
+        let mut ids: Vec<&String> = update.players.keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["p1", "p2", "p3", "p4", "p5"]);
+        assert!(client.get_player_state("stale").is_none());
     }
 }
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+// Snapshots the delay aims to keep buffered ahead of the render time
+const TARGET_BUFFERED: f64 = 2.0;
+pub const MIN_INTERPOLATION_DELAY: Duration = Duration::from_millis(50);
+pub const MAX_INTERPOLATION_DELAY: Duration = Duration::from_secs(1);
+
+// Adapts the interpolation delay to snapshot arrival timing: the mean gap times the
+// target buffer, plus headroom for the measured jitter, smoothed like TCP's RTT estimate
+#[derive(Clone, Debug, Default)]
+pub struct JitterBuffer {
+    last_arrival: Option<Instant>,
+    // Smoothed inter-arrival gap and its mean deviation, in seconds
+    mean_gap: Option<f64>,
+    deviation: f64,
+}
+
+impl JitterBuffer {
+    pub fn record_arrival(&mut self, at: Instant) {
+        let Some(last) = self.last_arrival.replace(at) else {
+            return;
+        };
+        let gap = at.saturating_duration_since(last).as_secs_f64();
+
+        match self.mean_gap {
+            None => {
+                self.mean_gap = Some(gap);
+                self.deviation = gap / 2.0;
+            }
+            Some(mean) => {
+                self.deviation += ((gap - mean).abs() - self.deviation) / 4.0;
+                self.mean_gap = Some(mean + (gap - mean) / 8.0);
+            }
+        }
+    }
+
+    // Smoothed deviation of the arrival gap from its mean
+    pub fn jitter(&self) -> Duration {
+        Duration::from_secs_f64(self.deviation)
+    }
+
+    // Something other than a snapshot showed the server is still sending, e.g. NoChange.
+    // Counts for staleness without sampling a gap
+    pub fn record_keepalive(&mut self, at: Instant) {
+        self.last_arrival = Some(at);
+    }
+
+    pub fn last_arrival(&self) -> Option<Instant> {
+        self.last_arrival
+    }
+
+    pub fn delay(&self) -> Duration {
+        let Some(mean) = self.mean_gap else {
+            return MIN_INTERPOLATION_DELAY;
+        };
+        let seconds = mean * TARGET_BUFFERED + 4.0 * self.deviation;
+        Duration::from_secs_f64(seconds).clamp(MIN_INTERPOLATION_DELAY, MAX_INTERPOLATION_DELAY)
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(buffer: &mut JitterBuffer, start: Instant, gaps_ms: &[u64]) -> Instant {
+        let mut at = start;
+        buffer.record_arrival(at);
+        for gap in gaps_ms {
+            at += Duration::from_millis(*gap);
+            buffer.record_arrival(at);
+        }
+        at
+    }
+
+    #[test]
+    fn test_delay_grows_with_jitter_and_shrinks_when_regular() {
+        let mut buffer = JitterBuffer::default();
+        assert_eq!(buffer.delay(), MIN_INTERPOLATION_DELAY);
+
+        // Bursty arrivals averaging 50ms apart
+        let irregular: Vec<u64> = (0..40).map(|i| if i % 2 == 0 { 5 } else { 95 }).collect();
+        let at = feed(&mut buffer, Instant::now(), &irregular);
+        let jittery_delay = buffer.delay();
+        assert!(
+            jittery_delay > Duration::from_millis(200),
+            "Jitter should add headroom, got {:?}",
+            jittery_delay
+        );
+
+        // Steady 16ms arrivals settle back to the floor
+        feed(&mut buffer, at, &[16; 100]);
+        assert!(buffer.delay() < jittery_delay);
+        assert_eq!(buffer.delay(), MIN_INTERPOLATION_DELAY);
+    }
+}
@@ -1,3 +1,11 @@
 mod game;
+mod jitter;
+mod quality;
+mod recording;
+mod view;
 
-pub use game::GameClient;
+pub use game::{ClientEvent, ConnectionState, GameClient, LatencyStats, MessageCallback};
+pub use jitter::{JitterBuffer, MAX_INTERPOLATION_DELAY, MIN_INTERPOLATION_DELAY};
+pub use quality::{ConnectionQuality, QualityInputs};
+pub use recording::{read_recording, RecordedMessage};
+pub use view::ViewTransform;
@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+// Thresholds at which each measurement drops the connection to Fair, then Poor
+const FAIR_RTT: Duration = Duration::from_millis(100);
+const POOR_RTT: Duration = Duration::from_millis(250);
+const FAIR_JITTER: Duration = Duration::from_millis(30);
+const POOR_JITTER: Duration = Duration::from_millis(100);
+const FAIR_GAP_RATE: f64 = 0.02;
+const POOR_GAP_RATE: f64 = 0.10;
+const FAIR_STALENESS: Duration = Duration::from_millis(250);
+const POOR_STALENESS: Duration = Duration::from_secs(1);
+// No snapshot for this long counts as a lost connection
+const LOST_STALENESS: Duration = Duration::from_secs(3);
+// Snapshot interval the staleness thresholds are set for; slower feeds, such as
+// spectators', scale them up in proportion
+const REFERENCE_INTERVAL: Duration = Duration::from_millis(50);
+
+// One summary of connection health, worst first when compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConnectionQuality {
+    Good,
+    Fair,
+    Poor,
+    Lost,
+}
+
+// The client's measurements that quality is judged from
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QualityInputs {
+    // Smoothed round trip time, None until a Pong arrives
+    pub rtt: Option<Duration>,
+    // Mean deviation in the gap between snapshot arrivals
+    pub jitter: Duration,
+    // Recent fraction of snapshots that never arrived, 0.0 to 1.0
+    pub gap_rate: f64,
+    // Time since the last snapshot or keepalive arrived, None before the first
+    pub staleness: Option<Duration>,
+    // How often the server sends snapshots, None if unknown
+    pub expected_interval: Option<Duration>,
+}
+
+impl ConnectionQuality {
+    // The worst rating any single measurement earns; missing ones don't count against it
+    pub fn classify(inputs: &QualityInputs) -> Self {
+        let stale = |threshold| scale_staleness(threshold, inputs.expected_interval);
+        if inputs
+            .staleness
+            .is_some_and(|staleness| staleness >= stale(LOST_STALENESS))
+        {
+            return ConnectionQuality::Lost;
+        }
+        [
+            inputs
+                .rtt
+                .map_or(ConnectionQuality::Good, |rtt| rate(rtt, FAIR_RTT, POOR_RTT)),
+            rate(inputs.jitter, FAIR_JITTER, POOR_JITTER),
+            rate(inputs.gap_rate, FAIR_GAP_RATE, POOR_GAP_RATE),
+            inputs
+                .staleness
+                .map_or(ConnectionQuality::Good, |staleness| {
+                    rate(staleness, stale(FAIR_STALENESS), stale(POOR_STALENESS))
+                }),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(ConnectionQuality::Good)
+    }
+}
+
+fn scale_staleness(threshold: Duration, interval: Option<Duration>) -> Duration {
+    let factor = interval.map_or(1.0, |interval| {
+        interval.as_secs_f64() / REFERENCE_INTERVAL.as_secs_f64()
+    });
+    threshold.mul_f64(factor.max(1.0))
+}
+
+fn rate<T: PartialOrd>(value: T, fair: T, poor: T) -> ConnectionQuality {
+    if value >= poor {
+        ConnectionQuality::Poor
+    } else if value >= fair {
+        ConnectionQuality::Fair
+    } else {
+        ConnectionQuality::Good
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inputs_classified_into_each_bucket() {
+        let good = QualityInputs {
+            rtt: Some(Duration::from_millis(40)),
+            jitter: Duration::from_millis(5),
+            gap_rate: 0.0,
+            staleness: Some(Duration::from_millis(20)),
+            expected_interval: None,
+        };
+        assert_eq!(ConnectionQuality::classify(&good), ConnectionQuality::Good);
+        // Nothing measured yet is no evidence of trouble
+        assert_eq!(
+            ConnectionQuality::classify(&QualityInputs::default()),
+            ConnectionQuality::Good
+        );
+
+        let fair = QualityInputs {
+            rtt: Some(Duration::from_millis(150)),
+            ..good
+        };
+        assert_eq!(ConnectionQuality::classify(&fair), ConnectionQuality::Fair);
+
+        // One bad measurement is enough
+        let poor = QualityInputs {
+            gap_rate: 0.2,
+            ..fair
+        };
+        assert_eq!(ConnectionQuality::classify(&poor), ConnectionQuality::Poor);
+        let poor = QualityInputs {
+            jitter: Duration::from_millis(120),
+            ..good
+        };
+        assert_eq!(ConnectionQuality::classify(&poor), ConnectionQuality::Poor);
+
+        let lost = QualityInputs {
+            staleness: Some(Duration::from_secs(5)),
+            ..good
+        };
+        assert_eq!(ConnectionQuality::classify(&lost), ConnectionQuality::Lost);
+    }
+
+    #[test]
+    fn test_staleness_scaled_to_snapshot_interval() {
+        // A spectator feed every 500ms is ten times the reference interval
+        let spectator = QualityInputs {
+            staleness: Some(Duration::from_millis(1500)),
+            expected_interval: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        assert_eq!(
+            ConnectionQuality::classify(&spectator),
+            ConnectionQuality::Good
+        );
+        let silent = QualityInputs {
+            staleness: Some(Duration::from_secs(30)),
+            ..spectator
+        };
+        assert_eq!(
+            ConnectionQuality::classify(&silent),
+            ConnectionQuality::Lost
+        );
+
+        // Faster feeds keep the base thresholds
+        let fast = QualityInputs {
+            staleness: Some(Duration::from_millis(1500)),
+            expected_interval: Some(Duration::from_millis(16)),
+            ..Default::default()
+        };
+        assert_eq!(ConnectionQuality::classify(&fast), ConnectionQuality::Poor);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::common::{ClientMessage, Result, ServerMessage, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+// One line of a client recording
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "direction")]
+pub enum RecordedMessage {
+    Sent {
+        at: Timestamp,
+        message: ClientMessage,
+    },
+    Received {
+        at: Timestamp,
+        message: ServerMessage,
+    },
+}
+
+// Appends every message a client sends or processes to a newline-delimited JSON file
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn record(&mut self, entry: &RecordedMessage) -> Result<()> {
+        let json = serde_json::to_string(entry)?;
+        writeln!(self.writer, "{}", json)?;
+        // Flush per line so a crash still leaves a usable recording
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordedMessage>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
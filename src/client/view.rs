@@ -0,0 +1,30 @@
+use crate::common::Vector2;
+
+// Maps server world coordinates onto a client's screen convention:
+// y is flipped first, then scaled, then offset
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewTransform {
+    pub offset: Vector2,
+    pub scale: f32,
+    pub flip_y: bool,
+}
+
+impl ViewTransform {
+    pub fn apply(&self, position: Vector2) -> Vector2 {
+        let y = if self.flip_y { -position.y } else { position.y };
+        Vector2 {
+            x: position.x * self.scale + self.offset.x,
+            y: y * self.scale + self.offset.y,
+        }
+    }
+}
+
+impl Default for ViewTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vector2 { x: 0.0, y: 0.0 },
+            scale: 1.0,
+            flip_y: false,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::messages::ErrorCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,6 +13,31 @@ pub enum GameError {
     GameError(String),
     #[error("Network error: {0}")]
     NetworkError(String),
+    #[error("Server error ({code:?}): {message}")]
+    ServerError { code: ErrorCode, message: String },
+    // Attaches where an error came from, e.g. "player 127.0.0.1:5000"
+    #[error("{context}: {source}")]
+    WithContext {
+        context: String,
+        source: Box<GameError>,
+    },
+}
+
+impl GameError {
+    pub fn with_context(self, context: impl Into<String>) -> Self {
+        GameError::WithContext {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    // The underlying error with any context stripped
+    pub fn root(&self) -> &GameError {
+        match self {
+            GameError::WithContext { source, .. } => source.root(),
+            other => other,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, GameError>;
@@ -0,0 +1,29 @@
+// Default tolerance for comparing simulation floats: positions, velocities and energies
+pub const EPSILON: f32 = 1e-4;
+
+// Absolute comparison near zero, relative once magnitudes pass 1.0, so large
+// coordinates aren't held to a tolerance finer than f32 can represent
+pub fn approx_eq_f32(a: f32, b: f32, eps: f32) -> bool {
+    let scale = a.abs().max(b.abs()).max(1.0);
+    (a - b).abs() <= eps * scale
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approx_eq_near_zero_and_large() {
+        // Near zero the tolerance is absolute
+        assert!(approx_eq_f32(0.0, 0.00005, EPSILON));
+        assert!(approx_eq_f32(-0.00005, 0.00004, EPSILON));
+        assert!(!approx_eq_f32(0.0, 0.001, EPSILON));
+
+        // Large magnitudes scale it, since adjacent f32s are further apart than EPSILON
+        assert!(approx_eq_f32(1_000_000.0, 1_000_000.0 + 50.0, EPSILON));
+        assert!(!approx_eq_f32(1_000_000.0, 1_000_200.0, EPSILON));
+
+        assert!(!approx_eq_f32(f32::NAN, f32::NAN, EPSILON));
+    }
+}
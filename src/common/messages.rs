@@ -1,8 +1,13 @@
+use super::errors::{GameError, Result};
 use super::types::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::mpsc::Sender;
 
+// Bump whenever the serialized shape of ClientMessage or ServerMessage changes.
+// The golden tests below fail until the new shape is recorded against the new version.
+pub const PROTOCOL_VERSION: u32 = 27;
+
 // Internal message type that includes non-serializable variants
 pub enum InternalMessage {
     NewConnection {
@@ -16,23 +21,955 @@ pub enum InternalMessage {
 }
 
 // Network message type that can be serialized
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
 pub enum ClientMessage {
-    Join,
-    Move { direction: Vector2 },
+    Join {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        // Stable client identity, so the server can spot a second connection for it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    Move {
+        direction: Vector2,
+        // Client clock in ms when the input was made, for server-side ordering
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_time: Option<u64>,
+        // Fraction of max speed in [0, 1]. When set, direction only steers and is
+        // normalized; when absent, direction is the velocity itself
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        throttle: Option<f32>,
+    },
+    // Position of a client-authoritative player; ignored for everyone else
+    ReportPosition {
+        position: Vector2,
+    },
+    Ping {
+        sent_at: Timestamp,
+    },
+    SetMeta {
+        key: String,
+        value: String,
+    },
+    SetName {
+        name: String,
+    },
+    Chat {
+        text: String,
+    },
+    // Watch without joining; snapshots arrive at the server's spectator rate
+    Spectate,
+    // Wraps a message the server must answer with Ack { seq }
+    Reliable {
+        seq: u64,
+        message: Box<ClientMessage>,
+    },
+    // Several messages in one frame, e.g. a frame's buffered inputs; handled in order
+    Batch {
+        messages: Vec<ClientMessage>,
+    },
     Disconnect,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
 pub enum ServerMessage {
-    JoinAccepted { player_id: String },
+    JoinAccepted {
+        player_id: String,
+        // The name the player joined with, which the server may have changed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
     GameState(GameStateUpdate),
-    Error { message: String },
+    // Changes since the snapshot at `base_tick`
+    GameStateDelta(GameStateDelta),
+    // Keepalive sent instead of a snapshot while the world is idle
+    NoChange {
+        tick: u64,
+    },
+    Pong {
+        sent_at: Timestamp,
+        server_time: Timestamp,
+    },
+    PlayerRenamed {
+        player_id: String,
+        name: String,
+    },
+    PlayerLeft {
+        player_id: String,
+    },
+    ChatMessage {
+        player_id: String,
+        text: String,
+    },
+    // Slot mapping for SlotSnapshot, sent on join (for every player) and leave
+    SlotAssigned {
+        slot: u32,
+        player_id: String,
+    },
+    SlotReleased {
+        slot: u32,
+    },
+    // Full snapshot keyed by slot instead of player id, when slot encoding is on
+    SlotSnapshot(SlotSnapshot),
+    Ack {
+        seq: u64,
+    },
+    // Client-relevant rules, sent once after JoinAccepted
+    WorldInfo(WorldInfo),
+    // Server-configured blob (map, rules...) sent once after JoinAccepted
+    InitData {
+        #[serde(with = "base64_bytes")]
+        payload: Vec<u8>,
+    },
+    Error {
+        error_code: ErrorCode,
+        message: String,
+    },
+    // Any variant this build doesn't know, so newer servers don't break older clients
+    #[serde(other)]
+    Unknown,
+}
+
+// Subset of the server's rules clients need to render the world
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WorldInfo {
+    pub map_bounds: (f32, f32),
+    pub max_velocity: f32,
+    // Server cadence, so clients can size their interpolation delay
+    pub tick_rate_ms: u64,
+    pub broadcast_rate_ms: u64,
+}
+
+// Encodes bytes as a base64 string rather than a JSON array of numbers
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+// Reads players sent either as a map or as (id, state) pairs, which servers use for
+// byte-stable sorted output
+mod players_map_or_pairs {
+    use super::PlayerState;
+    use serde::{Deserialize, Deserializer};
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Players {
+        Map(HashMap<String, PlayerState>),
+        Pairs(Vec<(String, PlayerState)>),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, PlayerState>, D::Error> {
+        Ok(match Players::deserialize(deserializer)? {
+            Players::Map(players) => players,
+            Players::Pairs(players) => players.into_iter().collect(),
+        })
+    }
+}
+
+// Machine-readable reason attached to server errors
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    ServerFull,
+    InvalidMetadata,
+    InvalidName,
+    InvalidChat,
+    RateLimited,
+    // Message not valid in the connection's current state, e.g. Move before Join
+    ProtocolError,
+    // Removed by the server; the message gives the reason
+    Kicked,
+    // Another live connection already holds this identity token
+    DuplicateConnection,
+    // Refused by the server's join validator; the message gives the reason
+    JoinRejected,
+    Other,
+}
+
+pub const MAX_NAME_LEN: usize = 16;
+pub const MAX_CHAT_LEN: usize = 256;
+
+// Display names are 1-16 characters of letters, digits, spaces, '_' or '-'
+pub fn validate_name(name: &str) -> Result<()> {
+    let length = name.chars().count();
+    if length == 0 || length > MAX_NAME_LEN {
+        return Err(GameError::GameError(format!(
+            "Name must be 1-{} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    if name.trim() != name {
+        return Err(GameError::GameError(
+            "Name cannot start or end with a space".into(),
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-')
+    {
+        return Err(GameError::GameError(
+            "Name may only contain letters, digits, spaces, '_' and '-'".into(),
+        ));
+    }
+    Ok(())
+}
+
+// Serializes `message` with every float rounded to `decimals` places, trading
+// sub-pixel precision for smaller snapshots. Clients parse the result as usual
+pub fn to_json_rounded<T: Serialize>(message: &T, decimals: u32) -> Result<String> {
+    let mut json = Vec::new();
+    let formatter = RoundingFormatter {
+        factor: 10f64.powi(decimals as i32),
+    };
+    message.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut json, formatter,
+    ))?;
+    String::from_utf8(json).map_err(|e| GameError::GameError(e.to_string()))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Compact output with floats rounded as they are written, in one pass
+struct RoundingFormatter {
+    factor: f64,
+}
+
+impl serde_json::ser::Formatter for RoundingFormatter {
+    fn write_f32<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: f32,
+    ) -> std::io::Result<()> {
+        let rounded = ((value as f64 * self.factor).round() / self.factor) as f32;
+        serde_json::ser::CompactFormatter.write_f32(writer, rounded)
+    }
+
+    fn write_f64<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: f64,
+    ) -> std::io::Result<()> {
+        let rounded = (value * self.factor).round() / self.factor;
+        serde_json::ser::CompactFormatter.write_f64(writer, rounded)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct GameStateUpdate {
     pub tick: u64,
+    #[serde(deserialize_with = "players_map_or_pairs::deserialize")]
     pub players: HashMap<String, PlayerState>,
     pub server_time: Timestamp,
+    // Large snapshots are split; this is part `part` (0-based) of `of` for the tick
+    #[serde(default)]
+    pub part: u32,
+    #[serde(default = "single_part")]
+    pub of: u32,
+    // Arena (min, max) corners, so a late joiner can render before WorldInfo arrives
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<(Vector2, Vector2)>,
+}
+
+fn single_part() -> u32 {
+    1
+}
+
+impl GameStateUpdate {
+    pub fn new(tick: u64, players: HashMap<String, PlayerState>, server_time: Timestamp) -> Self {
+        Self {
+            tick,
+            players,
+            server_time,
+            part: 0,
+            of: 1,
+            bounds: None,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.of <= 1
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SlotSnapshot {
+    pub tick: u64,
+    // (slot, state) pairs; a map would need string keys inside the tagged enum
+    pub players: Vec<(u32, PlayerState)>,
+    pub server_time: Timestamp,
+    // Split and bounded like GameStateUpdate
+    #[serde(default)]
+    pub part: u32,
+    #[serde(default = "single_part")]
+    pub of: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<(Vector2, Vector2)>,
+}
+
+impl SlotSnapshot {
+    // Maps slots back to player ids, skipping any slot not in `slots`
+    pub fn resolve(self, slots: &HashMap<u32, String>) -> GameStateUpdate {
+        let players = self
+            .players
+            .into_iter()
+            .filter_map(|(slot, state)| slots.get(&slot).map(|id| (id.clone(), state)))
+            .collect();
+        GameStateUpdate {
+            part: self.part,
+            of: self.of,
+            bounds: self.bounds,
+            ..GameStateUpdate::new(self.tick, players, self.server_time)
+        }
+    }
+}
+
+// Fields of one player that changed since the base snapshot; None means unchanged
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PlayerDelta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Vector2>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<Vector2>,
+    // Whole state for new players, or changes beyond position and velocity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full: Option<PlayerState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameStateDelta {
+    pub tick: u64,
+    pub base_tick: u64,
+    pub server_time: Timestamp,
+    // Unchanged players are omitted
+    pub changed: HashMap<String, PlayerDelta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+}
+
+impl GameStateDelta {
+    pub fn between(
+        base_tick: u64,
+        base: &HashMap<String, PlayerState>,
+        tick: u64,
+        current: &HashMap<String, PlayerState>,
+        server_time: Timestamp,
+    ) -> Self {
+        let mut changed = HashMap::new();
+        for (id, state) in current {
+            let delta = match base.get(id) {
+                Some(before) if before.visible_eq(state) => continue,
+                Some(before)
+                    if before.mass == state.mass
+                        && before.name == state.name
+                        && before.metadata == state.metadata
+                        && before.status == state.status
+                        && before.max_speed == state.max_speed =>
+                {
+                    PlayerDelta {
+                        position: (before.position != state.position).then_some(state.position),
+                        velocity: (before.velocity != state.velocity).then_some(state.velocity),
+                        full: None,
+                    }
+                }
+                _ => PlayerDelta {
+                    full: Some(state.clone()),
+                    ..Default::default()
+                },
+            };
+            changed.insert(id.clone(), delta);
+        }
+
+        let removed = base
+            .keys()
+            .filter(|id| !current.contains_key(*id))
+            .cloned()
+            .collect();
+
+        Self {
+            tick,
+            base_tick,
+            server_time,
+            changed,
+            removed,
+        }
+    }
+
+    // Applies the delta to the players of the base snapshot
+    pub fn apply_to(&self, players: &mut HashMap<String, PlayerState>) {
+        for id in &self.removed {
+            players.remove(id);
+        }
+        for (id, delta) in &self.changed {
+            if let Some(full) = &delta.full {
+                players.insert(id.clone(), full.clone());
+                continue;
+            }
+            if let Some(player) = players.get_mut(id) {
+                if let Some(position) = delta.position {
+                    player.position = position;
+                }
+                if let Some(velocity) = delta.velocity {
+                    player.velocity = velocity;
+                }
+            }
+        }
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::math::{approx_eq_f32, EPSILON};
+
+    #[test]
+    fn test_unknown_server_message_deserializes_to_unknown() {
+        let line = r#"{"type":"NewFeature","payload":{"x":1}}"#;
+        let message: ServerMessage =
+            serde_json::from_str(line).expect("Unknown variant should not error");
+        assert!(matches!(message, ServerMessage::Unknown));
+    }
+
+    #[test]
+    fn test_rounded_json_limits_decimals() {
+        let mut players = HashMap::new();
+        players.insert(
+            "p1".to_string(),
+            PlayerState {
+                position: Vector2 {
+                    x: 12.345678,
+                    y: -0.004,
+                },
+                velocity: Vector2 {
+                    x: 1.0 / 3.0,
+                    y: 7.0,
+                },
+                ..Default::default()
+            },
+        );
+        let message =
+            ServerMessage::GameState(GameStateUpdate::new(3, players, Timestamp::default()));
+
+        let json = to_json_rounded(&message, 2).unwrap();
+        let longest_fraction = json
+            .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .filter_map(|number| number.split_once('.'))
+            .map(|(_, fraction)| fraction.len())
+            .max();
+        assert_eq!(longest_fraction, Some(2), "{}", json);
+        assert!(json.len() < serde_json::to_string(&message).unwrap().len());
+
+        let ServerMessage::GameState(parsed) = serde_json::from_str(&json).unwrap() else {
+            panic!("Expected GameState");
+        };
+        let player = &parsed.players["p1"];
+        assert!(player.position.approx_eq(&Vector2 { x: 12.35, y: 0.0 }));
+        assert!(approx_eq_f32(player.velocity.x, 0.33, EPSILON));
+    }
+
+    #[test]
+    fn test_messages_use_type_tag() {
+        let json = serde_json::to_string(&ClientMessage::Join {
+            name: None,
+            token: None,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"type":"Join"}"#);
+
+        let json = serde_json::to_string(&ClientMessage::Move {
+            direction: Vector2 { x: 1.0, y: 0.0 },
+            client_time: None,
+            throttle: None,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"type":"Move","direction":{"x":1.0,"y":0.0}}"#);
+    }
+
+    #[test]
+    fn test_init_data_round_trips_as_base64() {
+        let payload: Vec<u8> = (0..=255).collect();
+        let json = serde_json::to_string(&ServerMessage::InitData {
+            payload: payload.clone(),
+        })
+        .unwrap();
+        match serde_json::from_str(&json).unwrap() {
+            ServerMessage::InitData { payload: decoded } => assert_eq!(decoded, payload),
+            other => panic!("Expected InitData, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_delta_round_trip_applies_changes() {
+        let moving = PlayerState {
+            velocity: Vector2 { x: 1.0, y: 0.0 },
+            ..Default::default()
+        };
+        let base = HashMap::from([
+            ("moving".to_string(), moving.clone()),
+            ("leaving".to_string(), PlayerState::default()),
+            ("idle".to_string(), PlayerState::default()),
+        ]);
+        let mut current = base.clone();
+        current.remove("leaving");
+        current.get_mut("moving").unwrap().position = Vector2 { x: 1.0, y: 0.0 };
+        current.insert("joined".to_string(), PlayerState::default());
+
+        let delta = GameStateDelta::between(1, &base, 2, &current, Timestamp::default());
+        assert!(!delta.changed.contains_key("idle"));
+        assert!(delta.changed["joined"].full.is_some());
+        assert_eq!(delta.removed, vec!["leaving".to_string()]);
+
+        let mut applied = base;
+        delta.apply_to(&mut applied);
+        assert_eq!(applied.len(), current.len());
+        for (id, state) in &current {
+            assert!(applied[id].visible_eq(state), "{} differs", id);
+        }
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("Player_1").is_ok());
+        assert!(validate_name("Blue Fox").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("ThisNameIsWayTooLong").is_err());
+        assert!(validate_name(" padded").is_err());
+        assert!(validate_name("semi;colon").is_err());
+    }
+
+    fn client_samples() -> Vec<(ClientMessage, &'static str)> {
+        vec![
+            (
+                ClientMessage::Join {
+                    name: None,
+                    token: None,
+                },
+                r#"{"type":"Join"}"#,
+            ),
+            (
+                ClientMessage::Join {
+                    name: Some("Alice".into()),
+                    token: None,
+                },
+                r#"{"type":"Join","name":"Alice"}"#,
+            ),
+            (
+                ClientMessage::Join {
+                    name: None,
+                    token: Some("abc123".into()),
+                },
+                r#"{"type":"Join","token":"abc123"}"#,
+            ),
+            (
+                ClientMessage::Move {
+                    direction: Vector2 { x: 1.0, y: -0.5 },
+                    client_time: None,
+                    throttle: None,
+                },
+                r#"{"type":"Move","direction":{"x":1.0,"y":-0.5}}"#,
+            ),
+            (
+                ClientMessage::Move {
+                    direction: Vector2 { x: 1.0, y: -0.5 },
+                    client_time: Some(1200),
+                    throttle: None,
+                },
+                r#"{"type":"Move","direction":{"x":1.0,"y":-0.5},"client_time":1200}"#,
+            ),
+            (
+                ClientMessage::Move {
+                    direction: Vector2 { x: 0.0, y: 1.0 },
+                    client_time: None,
+                    throttle: Some(0.5),
+                },
+                r#"{"type":"Move","direction":{"x":0.0,"y":1.0},"throttle":0.5}"#,
+            ),
+            (
+                ClientMessage::ReportPosition {
+                    position: Vector2 { x: 3.0, y: -4.0 },
+                },
+                r#"{"type":"ReportPosition","position":{"x":3.0,"y":-4.0}}"#,
+            ),
+            (
+                ClientMessage::Ping {
+                    sent_at: Timestamp::default(),
+                },
+                r#"{"type":"Ping","sent_at":0}"#,
+            ),
+            (
+                ClientMessage::SetMeta {
+                    key: "skin".into(),
+                    value: "red".into(),
+                },
+                r#"{"type":"SetMeta","key":"skin","value":"red"}"#,
+            ),
+            (
+                ClientMessage::SetName {
+                    name: "Alice".into(),
+                },
+                r#"{"type":"SetName","name":"Alice"}"#,
+            ),
+            (
+                ClientMessage::Chat { text: "hi".into() },
+                r#"{"type":"Chat","text":"hi"}"#,
+            ),
+            (
+                ClientMessage::Reliable {
+                    seq: 3,
+                    message: Box::new(ClientMessage::Disconnect),
+                },
+                r#"{"type":"Reliable","seq":3,"message":{"type":"Disconnect"}}"#,
+            ),
+            (
+                ClientMessage::Batch {
+                    messages: vec![
+                        ClientMessage::Chat { text: "hi".into() },
+                        ClientMessage::Disconnect,
+                    ],
+                },
+                r#"{"type":"Batch","messages":[{"type":"Chat","text":"hi"},{"type":"Disconnect"}]}"#,
+            ),
+            (ClientMessage::Spectate, r#"{"type":"Spectate"}"#),
+            (ClientMessage::Disconnect, r#"{"type":"Disconnect"}"#),
+        ]
+    }
+
+    fn server_samples() -> Vec<(ServerMessage, &'static str)> {
+        let mut player = PlayerState {
+            position: Vector2 { x: 1.0, y: 2.0 },
+            velocity: Vector2 { x: 0.5, y: 0.0 },
+            name: Some("Alice".into()),
+            ..Default::default()
+        };
+        player.metadata.insert("skin".into(), "red".into());
+        let mut players = HashMap::new();
+        players.insert("p1".to_string(), player);
+
+        vec![
+            (
+                ServerMessage::JoinAccepted {
+                    player_id: "p1".into(),
+                    name: None,
+                },
+                r#"{"type":"JoinAccepted","player_id":"p1"}"#,
+            ),
+            (
+                ServerMessage::JoinAccepted {
+                    player_id: "p1".into(),
+                    name: Some("Alice(2)".into()),
+                },
+                r#"{"type":"JoinAccepted","player_id":"p1","name":"Alice(2)"}"#,
+            ),
+            (
+                ServerMessage::GameState(GameStateUpdate::new(7, players, Timestamp::default())),
+                r#"{"type":"GameState","tick":7,"players":{"p1":{"position":{"x":1.0,"y":2.0},"velocity":{"x":0.5,"y":0.0},"last_update":0,"mass":1.0,"name":"Alice","metadata":{"skin":"red"},"status":"Active"}},"server_time":0,"part":0,"of":1}"#,
+            ),
+            (
+                ServerMessage::GameState(GameStateUpdate::new(
+                    7,
+                    HashMap::from([(
+                        "p1".to_string(),
+                        PlayerState {
+                            max_speed: Some(4.0),
+                            ..Default::default()
+                        },
+                    )]),
+                    Timestamp::default(),
+                )),
+                r#"{"type":"GameState","tick":7,"players":{"p1":{"position":{"x":0.0,"y":0.0},"velocity":{"x":0.0,"y":0.0},"last_update":0,"mass":1.0,"metadata":{},"status":"Active","max_speed":4.0}},"server_time":0,"part":0,"of":1}"#,
+            ),
+            (
+                ServerMessage::GameState(GameStateUpdate {
+                    bounds: Some((Vector2 { x: -1.0, y: -1.0 }, Vector2 { x: 1.0, y: 1.0 })),
+                    ..GameStateUpdate::new(7, HashMap::new(), Timestamp::default())
+                }),
+                r#"{"type":"GameState","tick":7,"players":{},"server_time":0,"part":0,"of":1,"bounds":[{"x":-1.0,"y":-1.0},{"x":1.0,"y":1.0}]}"#,
+            ),
+            (
+                ServerMessage::GameStateDelta(GameStateDelta {
+                    tick: 8,
+                    base_tick: 7,
+                    server_time: Timestamp::default(),
+                    changed: HashMap::from([(
+                        "p1".to_string(),
+                        PlayerDelta {
+                            position: Some(Vector2 { x: 1.5, y: 2.0 }),
+                            ..Default::default()
+                        },
+                    )]),
+                    removed: vec!["p2".to_string()],
+                }),
+                r#"{"type":"GameStateDelta","tick":8,"base_tick":7,"server_time":0,"changed":{"p1":{"position":{"x":1.5,"y":2.0}}},"removed":["p2"]}"#,
+            ),
+            (
+                ServerMessage::NoChange { tick: 8 },
+                r#"{"type":"NoChange","tick":8}"#,
+            ),
+            (
+                ServerMessage::Pong {
+                    sent_at: Timestamp::default(),
+                    server_time: Timestamp::default(),
+                },
+                r#"{"type":"Pong","sent_at":0,"server_time":0}"#,
+            ),
+            (
+                ServerMessage::PlayerRenamed {
+                    player_id: "p1".into(),
+                    name: "Alice".into(),
+                },
+                r#"{"type":"PlayerRenamed","player_id":"p1","name":"Alice"}"#,
+            ),
+            (
+                ServerMessage::PlayerLeft {
+                    player_id: "p1".into(),
+                },
+                r#"{"type":"PlayerLeft","player_id":"p1"}"#,
+            ),
+            (
+                ServerMessage::ChatMessage {
+                    player_id: "p1".into(),
+                    text: "hi".into(),
+                },
+                r#"{"type":"ChatMessage","player_id":"p1","text":"hi"}"#,
+            ),
+            (
+                ServerMessage::SlotAssigned {
+                    slot: 0,
+                    player_id: "p1".into(),
+                },
+                r#"{"type":"SlotAssigned","slot":0,"player_id":"p1"}"#,
+            ),
+            (
+                ServerMessage::SlotReleased { slot: 0 },
+                r#"{"type":"SlotReleased","slot":0}"#,
+            ),
+            (
+                ServerMessage::SlotSnapshot(SlotSnapshot {
+                    tick: 9,
+                    players: vec![(0, PlayerState::default())],
+                    server_time: Timestamp::default(),
+                    part: 0,
+                    of: 2,
+                    bounds: Some((Vector2 { x: -1.0, y: -1.0 }, Vector2 { x: 1.0, y: 1.0 })),
+                }),
+                r#"{"type":"SlotSnapshot","tick":9,"players":[[0,{"position":{"x":0.0,"y":0.0},"velocity":{"x":0.0,"y":0.0},"last_update":0,"mass":1.0,"metadata":{},"status":"Active"}]],"server_time":0,"part":0,"of":2,"bounds":[{"x":-1.0,"y":-1.0},{"x":1.0,"y":1.0}]}"#,
+            ),
+            (ServerMessage::Ack { seq: 3 }, r#"{"type":"Ack","seq":3}"#),
+            (
+                ServerMessage::WorldInfo(WorldInfo {
+                    map_bounds: (-100.0, 100.0),
+                    max_velocity: 10.0,
+                    tick_rate_ms: 16,
+                    broadcast_rate_ms: 16,
+                }),
+                r#"{"type":"WorldInfo","map_bounds":[-100.0,100.0],"max_velocity":10.0,"tick_rate_ms":16,"broadcast_rate_ms":16}"#,
+            ),
+            (
+                ServerMessage::InitData {
+                    payload: b"map".to_vec(),
+                },
+                r#"{"type":"InitData","payload":"bWFw"}"#,
+            ),
+            (
+                ServerMessage::Error {
+                    error_code: ErrorCode::ServerFull,
+                    message: "full".into(),
+                },
+                r#"{"type":"Error","error_code":"ServerFull","message":"full"}"#,
+            ),
+            (
+                ServerMessage::Error {
+                    error_code: ErrorCode::ProtocolError,
+                    message: "join first".into(),
+                },
+                r#"{"type":"Error","error_code":"ProtocolError","message":"join first"}"#,
+            ),
+            (
+                ServerMessage::Error {
+                    error_code: ErrorCode::Kicked,
+                    message: "restarting".into(),
+                },
+                r#"{"type":"Error","error_code":"Kicked","message":"restarting"}"#,
+            ),
+            (
+                ServerMessage::Error {
+                    error_code: ErrorCode::DuplicateConnection,
+                    message: "already connected".into(),
+                },
+                r#"{"type":"Error","error_code":"DuplicateConnection","message":"already connected"}"#,
+            ),
+            (
+                ServerMessage::Error {
+                    error_code: ErrorCode::JoinRejected,
+                    message: "not invited".into(),
+                },
+                r#"{"type":"Error","error_code":"JoinRejected","message":"not invited"}"#,
+            ),
+            (ServerMessage::Unknown, r#"{"type":"Unknown"}"#),
+        ]
+    }
+
+    // Shapes clients accept that ServerMessage never serializes to, such as the sorted
+    // players form the server can send instead of a map
+    fn server_parse_samples() -> Vec<(ServerMessage, &'static str)> {
+        let player = PlayerState {
+            position: Vector2 { x: 1.0, y: 2.0 },
+            ..Default::default()
+        };
+        let players = HashMap::from([
+            ("p1".to_string(), player.clone()),
+            ("p2".to_string(), player),
+        ]);
+        vec![(
+            ServerMessage::GameState(GameStateUpdate::new(7, players, Timestamp::default())),
+            r#"{"type":"GameState","tick":7,"players":[["p1",{"position":{"x":1.0,"y":2.0}}],["p2",{"position":{"x":1.0,"y":2.0}}]],"server_time":0,"part":0,"of":1}"#,
+        )]
+    }
+
+    // FNV-1a, stable across Rust releases unlike DefaultHasher
+    fn fingerprint(lines: &[&str]) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in lines.iter().flat_map(|line| line.bytes().chain([b'\n'])) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    #[test]
+    fn test_client_message_golden_serialization() {
+        for (message, expected) in client_samples() {
+            assert_eq!(serde_json::to_string(&message).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_server_message_golden_serialization() {
+        for (message, expected) in server_samples() {
+            assert_eq!(serde_json::to_string(&message).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_server_message_golden_parsing() {
+        for (expected, json) in server_parse_samples() {
+            assert_eq!(
+                serde_json::from_str::<ServerMessage>(json).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_protocol_version_matches_golden_shapes() {
+        let client = client_samples();
+        let server = server_samples();
+        let parsed = server_parse_samples();
+        let lines: Vec<&str> = client
+            .iter()
+            .map(|(_, json)| *json)
+            .chain(server.iter().chain(&parsed).map(|(_, json)| *json))
+            .collect();
+
+        // If this fails, the wire format changed: bump PROTOCOL_VERSION and record
+        // the new fingerprint here alongside it.
+        assert_eq!(
+            (PROTOCOL_VERSION, fingerprint(&lines)),
+            (27, 0xcd8c02556ba607e3),
+            "Wire format changed without bumping PROTOCOL_VERSION"
+        );
+    }
+
+    // Every variant must come back unchanged, so a field that serializes but fails
+    // to deserialize (or silently defaults) is caught before it reaches the wire
+    mod round_trip {
+        use super::*;
+
+        #[test]
+        fn test_client_messages_round_trip() {
+            for (message, _) in client_samples() {
+                let json = serde_json::to_string(&message).unwrap();
+                let parsed: ClientMessage = serde_json::from_str(&json).unwrap();
+                assert_eq!(parsed, message, "{}", json);
+            }
+        }
+
+        #[test]
+        fn test_server_messages_round_trip() {
+            for (message, _) in server_samples() {
+                let json = serde_json::to_string(&message).unwrap();
+                let parsed: ServerMessage = serde_json::from_str(&json).unwrap();
+                assert_eq!(parsed, message, "{}", json);
+            }
+        }
+
+        #[test]
+        fn test_populated_snapshot_round_trips() {
+            let players: HashMap<String, PlayerState> = (0..3)
+                .map(|i| {
+                    let mut player = PlayerState {
+                        position: Vector2 {
+                            x: i as f32 * 1.5,
+                            y: -2.25,
+                        },
+                        velocity: Vector2 {
+                            x: 0.125,
+                            y: i as f32,
+                        },
+                        last_update: Timestamp::from_millis(1000 + i),
+                        name: Some(format!("Player {}", i)),
+                        max_speed: Some(4.0),
+                        client_authoritative: i == 1,
+                        status: PlayerStatus::SpawnProtected,
+                        ..Default::default()
+                    };
+                    player.metadata.insert("team".into(), (i % 2).to_string());
+                    (format!("p{}", i), player)
+                })
+                .collect();
+            let snapshot = ServerMessage::GameState(GameStateUpdate {
+                part: 1,
+                of: 2,
+                bounds: Some((Vector2 { x: -50.0, y: -50.0 }, Vector2 { x: 50.0, y: 50.0 })),
+                ..GameStateUpdate::new(42, players.clone(), Timestamp::from_millis(5000))
+            });
+            let delta = ServerMessage::GameStateDelta(GameStateDelta {
+                tick: 43,
+                base_tick: 42,
+                server_time: Timestamp::from_millis(5016),
+                changed: players
+                    .into_iter()
+                    .map(|(id, state)| {
+                        let delta = PlayerDelta {
+                            velocity: Some(state.velocity),
+                            full: Some(state),
+                            ..Default::default()
+                        };
+                        (id, delta)
+                    })
+                    .collect(),
+                removed: vec!["p9".into()],
+            });
+
+            for message in [snapshot, delta] {
+                let json = serde_json::to_string(&message).unwrap();
+                assert_eq!(
+                    serde_json::from_str::<ServerMessage>(&json).unwrap(),
+                    message
+                );
+            }
+        }
+    }
 }
@@ -1,4 +1,5 @@
 mod errors;
+pub mod math;
 mod messages;
 mod types;
 
@@ -1,7 +1,11 @@
+use super::math::{approx_eq_f32, EPSILON};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Sub;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+// Milliseconds since the Unix epoch; ordered by that value
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Timestamp(u64);
 
 impl Timestamp {
@@ -14,15 +18,40 @@ impl Timestamp {
         )
     }
     pub fn duration_since(&self, other: &Timestamp) -> Duration {
-        Duration::from_millis((self.0.saturating_sub(other.0)) as u64)
+        Duration::from_millis(self.0.saturating_sub(other.0))
     }
 
     pub fn elapsed(&self) -> Duration {
         Timestamp::now().duration_since(self)
     }
+
+    pub fn from_millis(millis: u64) -> Self {
+        Self(millis)
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.0
+    }
+}
+
+// Like duration_since: zero when the right side is later
+impl Sub for &Timestamp {
+    type Output = Duration;
+
+    fn sub(self, other: &Timestamp) -> Duration {
+        self.duration_since(other)
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default, Copy)]
+impl Sub for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, other: Timestamp) -> Duration {
+        &self - &other
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Copy, PartialEq)]
 pub struct Vector2 {
     pub x: f32,
     pub y: f32,
@@ -41,11 +70,199 @@ impl Vector2 {
             y: self.y * factor,
         }
     }
+    // Point `t` of the way from self to other
+    pub fn lerp(&self, other: &Vector2, t: f32) -> Vector2 {
+        Vector2 {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+        }
+    }
+    // Cheaper than distance when only comparing
+    pub fn distance_squared(&self, other: &Vector2) -> f32 {
+        self.sub(other).magnitude_squared()
+    }
+    pub fn distance(&self, other: &Vector2) -> f32 {
+        self.sub(other).magnitude()
+    }
+    pub fn sub(&self, other: &Vector2) -> Vector2 {
+        Vector2 {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+    pub fn magnitude_squared(&self) -> f32 {
+        self.x * self.x + self.y * self.y
+    }
+    pub fn magnitude(&self) -> f32 {
+        self.magnitude_squared().sqrt()
+    }
+    #[deprecated(note = "use magnitude")]
+    pub fn length(&self) -> f32 {
+        self.magnitude()
+    }
+    // Unit vector in the same direction; vectors shorter than EPSILON become zero
+    pub fn normalize(&self) -> Vector2 {
+        let magnitude = self.magnitude();
+        if magnitude < EPSILON {
+            return Vector2::default();
+        }
+        self.scale(1.0 / magnitude)
+    }
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+    pub fn approx_eq(&self, other: &Vector2) -> bool {
+        approx_eq_f32(self.x, other.x, EPSILON) && approx_eq_f32(self.y, other.y, EPSILON)
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct PlayerState {
+    // Defaulted so snapshots trimmed by a server field allowlist still parse
+    #[serde(default)]
     pub position: Vector2,
+    #[serde(default)]
     pub velocity: Vector2,
+    #[serde(default)]
     pub last_update: Timestamp,
+    // Heavier players push lighter ones further apart in collisions
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Game-specific key/value data (skin, score, class...) set by the client. Ordered by
+    // key, so equal states always serialize to the same bytes
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    pub status: PlayerStatus,
+    // Per-axis speed cap overriding GameRules::max_velocity, e.g. for power-ups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_speed: Option<f32>,
+    // The server takes this player's position from ReportPosition instead of simulating it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub client_authoritative: bool,
+}
+
+impl PlayerState {
+    // Whether clients would see the same player, ignoring last_update
+    pub fn visible_eq(&self, other: &PlayerState) -> bool {
+        self.position == other.position
+            && self.velocity == other.velocity
+            && self.mass == other.mass
+            && self.name == other.name
+            && self.metadata == other.metadata
+            && self.status == other.status
+            && self.max_speed == other.max_speed
+            && self.client_authoritative == other.client_authoritative
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlayerStatus {
+    #[default]
+    Active,
+    // Just spawned; ignored by collisions until the grace period ends
+    SpawnProtected,
+}
+
+fn default_mass() -> f32 {
+    1.0
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            position: Vector2::default(),
+            velocity: Vector2::default(),
+            last_update: Timestamp::default(),
+            mass: default_mass(),
+            name: None,
+            metadata: BTreeMap::new(),
+            status: PlayerStatus::default(),
+            max_speed: None,
+            client_authoritative: false,
+        }
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_magnitude_of_three_four_five() {
+        let v = Vector2 { x: 3.0, y: 4.0 };
+        assert_eq!(v.magnitude_squared(), 25.0);
+        assert_eq!(v.magnitude(), 5.0);
+        #[allow(deprecated)]
+        let length = v.length();
+        assert_eq!(length, 5.0);
+        assert!(v.normalize().approx_eq(&Vector2 { x: 0.6, y: 0.8 }));
+        assert_eq!(
+            Vector2 { x: 4.0, y: 6.0 }.sub(&Vector2 { x: 1.0, y: 2.0 }),
+            v
+        );
+    }
+
+    #[test]
+    fn test_distance_between_points() {
+        let (a, b) = (Vector2::default(), Vector2 { x: 3.0, y: 4.0 });
+        assert_eq!(a.distance(&b), 5.0);
+        assert_eq!(b.distance(&a), 5.0);
+        assert_eq!(a.distance_squared(&b), 25.0);
+    }
+
+    #[test]
+    fn test_normalize_zero_vector_is_zero() {
+        let normalized = Vector2::default().normalize();
+        assert_eq!(normalized, Vector2::default());
+        assert!(!normalized.x.is_nan() && !normalized.y.is_nan());
+        // Below EPSILON counts as zero too
+        assert_eq!(Vector2 { x: 1e-6, y: 0.0 }.normalize(), Vector2::default());
+    }
+
+    #[test]
+    fn test_timestamps_ordered_by_time() {
+        let earlier = Timestamp::now();
+        std::thread::sleep(Duration::from_millis(5));
+        let later = Timestamp::now();
+
+        assert!(later > earlier);
+        assert_eq!(earlier.clone().max(later.clone()), later);
+        assert!(&later - &earlier >= Duration::from_millis(5));
+        // Subtraction saturates rather than going negative
+        assert_eq!(earlier - later, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_player_state_equality() {
+        let player = || {
+            let mut state = PlayerState {
+                position: Vector2 { x: 1.0, y: 2.0 },
+                name: Some("Alice".into()),
+                last_update: Timestamp::from_millis(500),
+                ..Default::default()
+            };
+            state.metadata.insert("skin".into(), "red".into());
+            state
+        };
+        assert_eq!(player(), player());
+
+        // Full equality includes last_update; visible_eq ignores it
+        let later = PlayerState {
+            last_update: Timestamp::from_millis(900),
+            ..player()
+        };
+        assert_ne!(later, player());
+        assert!(later.visible_eq(&player()));
+
+        // Clients see the authority flag, so deltas must carry changes to it
+        let authoritative = PlayerState {
+            client_authoritative: true,
+            ..player()
+        };
+        assert!(!authoritative.visible_eq(&player()));
+    }
 }
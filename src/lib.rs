@@ -3,7 +3,7 @@ pub mod common;
 pub mod server;
 
 pub mod logging {
-    use tracing_subscriber::{fmt, EnvFilter};
+    use tracing_subscriber::EnvFilter;
 
     pub fn init() {
         tracing_subscriber::fmt()
@@ -0,0 +1,59 @@
+use super::game::GameState;
+use crate::common::Vector2;
+
+// Ticks a wandering bot keeps its heading before picking a new one
+const WANDER_TURN_TICKS: u32 = 60;
+
+// How a server-driven player picks its velocity each tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotBehavior {
+    // Drift in a random direction at half speed, turning every so often
+    Wander,
+    // Head for the closest other player at full speed
+    ChaseNearest,
+}
+
+#[derive(Debug)]
+pub(super) struct Bot {
+    behavior: BotBehavior,
+    heading: Vector2,
+    ticks_until_turn: u32,
+}
+
+impl Bot {
+    pub(super) fn new(behavior: BotBehavior) -> Self {
+        Self {
+            behavior,
+            heading: Vector2::default(),
+            ticks_until_turn: 0,
+        }
+    }
+
+    // The velocity bot `id` should move at this tick, None if it isn't in the game
+    pub(super) fn steer(&mut self, id: &str, state: &mut GameState) -> Option<Vector2> {
+        let position = state.get_player_state(id)?.position;
+        let max_velocity = state.rules().max_velocity;
+
+        match self.behavior {
+            BotBehavior::Wander => {
+                if self.ticks_until_turn == 0 {
+                    self.heading = state.random_heading();
+                    self.ticks_until_turn = WANDER_TURN_TICKS;
+                }
+                self.ticks_until_turn -= 1;
+                Some(self.heading.scale(max_velocity * 0.5))
+            }
+            BotBehavior::ChaseNearest => {
+                let Some((target, _)) = state.nearest_player(position, Some(id)) else {
+                    return Some(Vector2::default());
+                };
+                let target = state.get_player_state(&target)?.position;
+                let toward = Vector2 {
+                    x: target.x - position.x,
+                    y: target.y - position.y,
+                };
+                Some(toward.normalize().scale(max_velocity))
+            }
+        }
+    }
+}
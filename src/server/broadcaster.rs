@@ -0,0 +1,196 @@
+use super::projection::{ProjectedDelta, ProjectedMessage, ProjectedUpdate, SnapshotField};
+use crate::common::{to_json_rounded, Result, ServerMessage};
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{mpsc::Sender, Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+// Serializes a message as one newline-terminated line, rounding floats if asked to
+pub(super) fn encode<T: Serialize>(message: &T, float_precision: Option<u32>) -> Result<Vec<u8>> {
+    let json = match float_precision {
+        Some(decimals) => to_json_rounded(message, decimals)?,
+        None => serde_json::to_string(message)?,
+    };
+    Ok(format!("{}\n", json).into_bytes())
+}
+
+// How per-tick state is serialized, from the server's settings
+#[derive(Clone, Debug, Default)]
+pub(super) struct StateEncoding {
+    pub float_precision: Option<u32>,
+    pub snapshot_fields: Option<HashSet<SnapshotField>>,
+    pub sorted_players: bool,
+}
+
+// Like encode, with snapshots and deltas trimmed to the field allowlist and players
+// sorted as the encoding asks
+pub(super) fn encode_state(message: &ServerMessage, encoding: &StateEncoding) -> Result<Vec<u8>> {
+    let fields = encoding.snapshot_fields.as_ref();
+    match (message, fields) {
+        (ServerMessage::GameState(update), _) if fields.is_some() || encoding.sorted_players => {
+            encode(
+                &ProjectedMessage::GameState(ProjectedUpdate::new(
+                    update,
+                    fields,
+                    encoding.sorted_players,
+                )),
+                encoding.float_precision,
+            )
+        }
+        (ServerMessage::GameStateDelta(delta), Some(fields)) => encode(
+            &ProjectedMessage::GameStateDelta(ProjectedDelta::new(delta, fields)),
+            encoding.float_precision,
+        ),
+        _ => encode(message, encoding.float_precision),
+    }
+}
+
+type Recipients = Vec<Sender<Vec<u8>>>;
+
+// Work for the broadcast thread, sent in the order submitted
+pub(super) enum BroadcastJob {
+    // One tick's state broadcasts, each message with the connections it goes to
+    State {
+        messages: Vec<(ServerMessage, Recipients)>,
+        encoding: StateEncoding,
+    },
+    // An encoded one-off message such as PlayerLeft, queued behind earlier state so
+    // clients see the two in order
+    Message {
+        bytes: Vec<u8>,
+        recipients: Recipients,
+    },
+}
+
+type Deliver = Box<dyn Fn(&Sender<Vec<u8>>, Vec<u8>) + Send>;
+
+#[derive(Default)]
+struct Shared {
+    // Jobs not yet started. A tick's state replaces the previous tick's while that is
+    // still last in line, so a slow thread skips stale ticks; one-off messages are
+    // never dropped
+    queue: VecDeque<BroadcastJob>,
+    // Sizes of sent messages, collected into ServerStats by the game loop
+    sizes: Vec<usize>,
+    shutdown: bool,
+}
+
+// Serializes and sends state broadcasts on its own thread so the simulation keeps
+// its cadence when they are slow
+pub(super) struct Broadcaster {
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Broadcaster {
+    pub fn spawn() -> Self {
+        Self::with_deliver(|sender, bytes| {
+            // A closed channel is cleaned up when its reader reports the disconnect
+            let _ = sender.send(bytes);
+        })
+    }
+
+    // `deliver` hands one encoded message to one connection
+    pub fn with_deliver(deliver: impl Fn(&Sender<Vec<u8>>, Vec<u8>) + Send + 'static) -> Self {
+        let shared = Arc::new((Mutex::new(Shared::default()), Condvar::new()));
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || run(&thread_shared, Box::new(deliver)));
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn submit(&self, job: BroadcastJob) {
+        let mut shared = self.lock();
+        if matches!(job, BroadcastJob::State { .. }) && shared.has_pending_state() {
+            shared.queue.pop_back();
+        }
+        shared.queue.push_back(job);
+        drop(shared);
+        self.shared.1.notify_one();
+    }
+
+    // Whether the next state submitted will replace one that was never sent, in which
+    // case it must not depend on the replaced one (a delta or NoChange)
+    pub fn has_pending_state(&self) -> bool {
+        self.lock().has_pending_state()
+    }
+
+    // Sizes of messages sent since the last call
+    pub fn take_sizes(&self) -> Vec<usize> {
+        std::mem::take(&mut self.lock().sizes)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        lock(&self.shared.0)
+    }
+}
+
+impl Shared {
+    fn has_pending_state(&self) -> bool {
+        matches!(self.queue.back(), Some(BroadcastJob::State { .. }))
+    }
+}
+
+// The thread's work never panics while holding the lock, so poisoning is harmless
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn run(shared: &(Mutex<Shared>, Condvar), deliver: Deliver) {
+    let (state, wake) = shared;
+    loop {
+        let job = {
+            let mut state = lock(state);
+            while state.queue.is_empty() && !state.shutdown {
+                state = wake
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            // Queued jobs are still sent on shutdown
+            match state.queue.pop_front() {
+                Some(job) => job,
+                None => return,
+            }
+        };
+
+        match job {
+            BroadcastJob::State { messages, encoding } => {
+                let mut sizes = Vec::new();
+                for (message, recipients) in &messages {
+                    match encode_state(message, &encoding) {
+                        Ok(bytes) => {
+                            sizes.push(bytes.len());
+                            for recipient in recipients {
+                                deliver(recipient, bytes.clone());
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to serialize broadcast: {}", e),
+                    }
+                }
+                lock(state).sizes.extend(sizes);
+            }
+            // Already counted in the stats when it was encoded
+            BroadcastJob::Message { bytes, recipients } => {
+                for recipient in &recipients {
+                    deliver(recipient, bytes.clone());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        self.lock().shutdown = true;
+        self.shared.1.notify_one();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                eprintln!("Broadcast thread panicked");
+            }
+        }
+    }
+}
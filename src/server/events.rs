@@ -0,0 +1,105 @@
+use std::{collections::VecDeque, ops::RangeBounds};
+
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventKind {
+    PlayerJoined { player_id: String },
+    PlayerLeft { player_id: String },
+    Eliminated { player_id: String },
+    Collision { player1: String, player2: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub tick: u64,
+    pub kind: EventKind,
+}
+
+// Ring buffer of recent significant events, oldest dropped first
+#[derive(Debug)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_LOG_CAPACITY)
+    }
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, kind: EventKind) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event { tick, kind });
+    }
+
+    pub fn in_range(&self, range: impl RangeBounds<u64>) -> Vec<Event> {
+        self.events
+            .iter()
+            .filter(|event| range.contains(&event.tick))
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(player_id: &str) -> EventKind {
+        EventKind::PlayerJoined {
+            player_id: player_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_query_tick_range() {
+        let mut log = EventLog::default();
+        log.record(50, joined("early"));
+        log.record(100, joined("start"));
+        log.record(150, joined("middle"));
+        log.record(200, joined("end"));
+        log.record(250, joined("late"));
+
+        let ticks: Vec<u64> = log.in_range(100..=200).iter().map(|e| e.tick).collect();
+        assert_eq!(ticks, vec![100, 150, 200]);
+
+        let ticks: Vec<u64> = log.in_range(100..200).iter().map(|e| e.tick).collect();
+        assert_eq!(ticks, vec![100, 150]);
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let mut log = EventLog::new(3);
+        for tick in 0..5 {
+            log.record(tick, joined("player"));
+        }
+
+        assert_eq!(log.len(), 3);
+        let ticks: Vec<u64> = log.in_range(..).iter().map(|e| e.tick).collect();
+        assert_eq!(ticks, vec![2, 3, 4]);
+    }
+}
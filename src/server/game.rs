@@ -1,15 +1,34 @@
+use super::bots::{Bot, BotBehavior};
+use super::broadcaster::{encode, encode_state, BroadcastJob, Broadcaster, StateEncoding};
+use super::events::{Event, EventKind, EventLog};
+use super::history::PositionHistory;
+use super::integrator::{EulerIntegrator, Integrator};
+use super::network::{self, NetworkConfig};
+use super::projection::SnapshotField;
+use super::rate_limit::TokenBucket;
+use super::slots::SlotTable;
+use super::stats::ServerStats;
 use crate::common::{
-    ClientMessage, GameError, GameStateUpdate, InternalMessage, PlayerState, Result, ServerMessage,
-    Timestamp, Vector2,
+    validate_name, ClientMessage, ErrorCode, GameError, GameStateDelta, GameStateUpdate,
+    InternalMessage, PlayerState, PlayerStatus, Result, ServerMessage, SlotSnapshot, Timestamp,
+    Vector2, WorldInfo, MAX_CHAT_LEN, MAX_NAME_LEN,
 };
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::Serialize;
 use std::{
-    collections::{HashMap, VecDeque},
-    sync::mpsc::{Receiver, Sender},
-    time::Duration,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    net::{SocketAddr, TcpListener},
+    ops::RangeBounds,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 #[derive(Debug)]
 pub struct PlayerInput {
+    // Server-clock time the input was made, from client_time plus the player's skew
     timestamp: Timestamp,
     message: ClientMessage,
 }
@@ -17,14 +36,165 @@ pub struct PlayerInput {
 pub struct Player {
     sender: Sender<Vec<u8>>,
     input_queue: VecDeque<PlayerInput>,
-    state: PlayerState,
+    chat_bucket: TokenBucket,
 }
 
+// Where newly joined players are placed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpawnStrategy {
+    #[default]
+    Origin,
+    Random,
+}
+
+// How players interact with the map bounds
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BoundaryMode {
+    // Stop at the wall, losing velocity into it
+    #[default]
+    Clamp,
+    // Reflect off the wall, keeping `restitution` of the speed
+    Bounce {
+        restitution: f32,
+    },
+    // Ring out: players who leave the arena are removed from the game
+    Eliminate,
+}
+
+// What happens when a player picks a name another player already has
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamePolicy {
+    #[default]
+    Allow,
+    Reject,
+    // Append the lowest free " n", starting at 2, so the result is still a valid name
+    Suffix,
+}
+
+// What happens when a join presents an identity token another live connection holds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // Kick the old connection, e.g. one left half-open by a client that reconnected
+    #[default]
+    Supersede,
+    Reject,
+}
+
+// What happens to an input that arrives while a player's input queue is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // Keep the most recent inputs, which matter most for movement
+    #[default]
+    DropOldest,
+    DropNewest,
+    Disconnect,
+}
+
+// Mapped input times are kept within this of their arrival, so a client can't order
+// its inputs arbitrarily far ahead of or behind everyone else's
+const MAX_INPUT_TIME_DRIFT: Duration = Duration::from_secs(1);
+
+// Extra travel time allowed per position report, since network jitter bunches reports
+const REPORT_TIME_SLACK: Duration = Duration::from_millis(100);
+
+// Random spawn candidates tried before accepting one that is too close
+const MAX_SPAWN_ATTEMPTS: u32 = 16;
+
 // Game rules configuration
+#[derive(Clone, Debug)]
 pub struct GameRules {
     pub map_bounds: (f32, f32),
     pub max_velocity: f32,
     pub collision_radius: f32,
+    // Fraction of the overlap resolved per collision, split by mass
+    pub separation_strength: f32,
+    pub boundary_mode: BoundaryMode,
+    pub integrator: Arc<dyn Integrator>,
+    pub spawn_strategy: SpawnStrategy,
+    // Spawns closer than this to an existing player are retried
+    pub min_spawn_separation: f32,
+    // Limits on client-set player metadata
+    pub max_metadata_keys: usize,
+    pub max_metadata_len: usize,
+    // Joins beyond this many players are rejected, unlimited when None
+    pub max_players: Option<usize>,
+    // Fixed seed for reproducible randomness, entropy-seeded when None
+    pub rng_seed: Option<u64>,
+    // Sandbox/racing modes skip collision detection entirely
+    pub enable_collisions: bool,
+    // Longer frames (debugger pause, stalls) are clamped so players can't tunnel
+    pub max_delta: Duration,
+    // Newly spawned players are excluded from collisions for this long
+    pub spawn_grace: Duration,
+    // Each player may send `chat_limit` chat messages per `chat_interval`
+    pub chat_limit: u32,
+    pub chat_interval: Duration,
+    pub name_policy: NamePolicy,
+    // Inputs buffered per player between ticks, and what to do beyond that
+    pub max_input_queue: usize,
+    pub input_overflow: OverflowPolicy,
+    pub duplicate_policy: DuplicatePolicy,
+    // Testing mode: the game loop steps by this fixed delta as fast as it can,
+    // with no wall-clock pacing. None runs in real time
+    pub headless_delta: Option<Duration>,
+    // Ticks of per-player position history kept for lag compensation
+    pub history_ticks: usize,
+    // How long a player who joined with an identity token stays in the world after
+    // disconnecting, so a reconnect can find them. Zero removes them right away
+    pub reconnect_grace: Duration,
+    // Players who send no input for this long slow down at stop_deceleration
+    // (units/s^2) until they stop. None keeps velocity until the next input
+    pub input_timeout: Option<Duration>,
+    pub stop_deceleration: f32,
+}
+
+impl GameRules {
+    // The map as (min, max) corners; map_bounds applies to both axes
+    pub fn bounds(&self) -> (Vector2, Vector2) {
+        let (min, max) = self.map_bounds;
+        (Vector2 { x: min, y: min }, Vector2 { x: max, y: max })
+    }
+
+    // The same rules for a map `factor` times the size. Distances and speeds scale
+    // together so crossing the map or closing on a player takes as long as before
+    pub fn scaled(&self, factor: f32) -> GameRules {
+        let (min, max) = self.map_bounds;
+        GameRules {
+            map_bounds: (min * factor, max * factor),
+            max_velocity: self.max_velocity * factor,
+            collision_radius: self.collision_radius * factor,
+            min_spawn_separation: self.min_spawn_separation * factor,
+            stop_deceleration: self.stop_deceleration * factor,
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: &str| Err(GameError::GameError(message.into()));
+        if self.max_players == Some(0) {
+            return invalid("Max players must be at least 1");
+        }
+        let (min, max) = self.map_bounds;
+        if min >= max {
+            return invalid("Map bounds must have min below max");
+        }
+        if self.max_velocity < 0.0 || self.collision_radius < 0.0 {
+            return invalid("Max velocity and collision radius can't be negative");
+        }
+        Ok(())
+    }
+
+    // Client-relevant rules at the default cadence; GameServer::world_info
+    // reports the server's configured rates instead
+    pub fn world_info(&self) -> WorldInfo {
+        let default_rate = DEFAULT_TICK_RATE.as_millis() as u64;
+        WorldInfo {
+            map_bounds: self.map_bounds,
+            max_velocity: self.max_velocity,
+            tick_rate_ms: default_rate,
+            broadcast_rate_ms: default_rate,
+        }
+    }
 }
 
 impl Default for GameRules {
@@ -33,6 +203,29 @@ impl Default for GameRules {
             map_bounds: (-100.0, 100.0),
             max_velocity: 10.0,
             collision_radius: 10.0,
+            separation_strength: 1.0,
+            boundary_mode: BoundaryMode::Clamp,
+            integrator: Arc::new(EulerIntegrator),
+            spawn_strategy: SpawnStrategy::Origin,
+            min_spawn_separation: 0.0,
+            max_metadata_keys: 8,
+            max_metadata_len: 64,
+            max_players: None,
+            rng_seed: None,
+            enable_collisions: true,
+            max_delta: Duration::from_millis(100),
+            spawn_grace: Duration::from_millis(500),
+            chat_limit: 5,
+            chat_interval: Duration::from_secs(1),
+            name_policy: NamePolicy::Allow,
+            max_input_queue: 32,
+            input_overflow: OverflowPolicy::DropOldest,
+            duplicate_policy: DuplicatePolicy::Supersede,
+            headless_delta: None,
+            history_ticks: 64,
+            reconnect_grace: Duration::ZERO,
+            input_timeout: None,
+            stop_deceleration: 20.0,
         }
     }
 }
@@ -40,111 +233,360 @@ impl Default for GameRules {
 #[derive(Clone, Debug)]
 pub struct GameState {
     players: HashMap<String, PlayerState>,
+    // Velocity each player integrated with last tick
+    previous_velocities: HashMap<String, Vector2>,
+    // Pairs that collided during the last update
+    collisions: Vec<(String, String)>,
+    // Players removed for leaving the arena during the last update
+    eliminated: Vec<String>,
+    // Remaining spawn protection per player
+    spawn_grace: HashMap<String, Duration>,
+    // Time left before each player's last input counts as stopped
+    active_input: HashMap<String, Duration>,
     last_update: Timestamp,
+    rules: GameRules,
+    rng: SmallRng,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameState {
     pub fn new() -> Self {
+        Self::with_rules(GameRules::default())
+    }
+
+    pub fn with_rules(rules: GameRules) -> Self {
+        let rng = match rules.rng_seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        };
+
         Self {
             players: HashMap::new(),
+            previous_velocities: HashMap::new(),
+            collisions: Vec::new(),
+            eliminated: Vec::new(),
+            spawn_grace: HashMap::new(),
+            active_input: HashMap::new(),
             last_update: Timestamp::now(),
+            rules,
+            rng,
+        }
+    }
+
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    pub fn spawn_position(&mut self) -> Vector2 {
+        let mut candidate = self.spawn_candidate();
+        if self.rules.min_spawn_separation <= 0.0 {
+            return candidate;
+        }
+
+        for _ in 1..MAX_SPAWN_ATTEMPTS {
+            if self.is_spawn_clear(candidate) {
+                return candidate;
+            }
+            candidate = self.spawn_candidate();
+        }
+
+        if !self.is_spawn_clear(candidate) {
+            println!(
+                "No spawn point {} away from other players after {} attempts",
+                self.rules.min_spawn_separation, MAX_SPAWN_ATTEMPTS
+            );
+        }
+        candidate
+    }
+
+    fn is_spawn_clear(&self, candidate: Vector2) -> bool {
+        let min_distance_squared = self.rules.min_spawn_separation.powi(2);
+        self.players
+            .values()
+            .all(|player| player.position.distance_squared(&candidate) >= min_distance_squared)
+    }
+
+    // A random unit vector from the game's rng, so seeded games stay reproducible
+    pub(super) fn random_heading(&mut self) -> Vector2 {
+        let angle = self.rng.gen_range(0.0..std::f32::consts::TAU);
+        Vector2 {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+
+    fn spawn_candidate(&mut self) -> Vector2 {
+        match self.rules.spawn_strategy {
+            SpawnStrategy::Origin => Vector2::default(),
+            SpawnStrategy::Random => {
+                let (min, max) = self.rules.map_bounds;
+                Vector2 {
+                    x: self.rng.gen_range(min..=max),
+                    y: self.rng.gen_range(min..=max),
+                }
+            }
         }
     }
 
     pub fn update(&mut self, delta_time: Duration) -> Result<()> {
-        let rules = GameRules::default();
+        let rules = self.rules.clone();
+        let delta_time = delta_time.min(rules.max_delta);
+        self.decay_idle_velocities(delta_time, &rules);
 
         // First pass: Update positions
-        for player_state in self.players.values_mut() {
+        for (player_id, player_state) in self.players.iter_mut() {
+            // Positions of these come from the client's reports
+            if player_state.client_authoritative {
+                continue;
+            }
             // Update position based on velocity
-            let position_delta = player_state.velocity.scale(delta_time.as_secs_f32());
-            player_state.position = player_state.position.add(&position_delta);
+            let previous_velocity = self
+                .previous_velocities
+                .insert(player_id.clone(), player_state.velocity)
+                .unwrap_or(player_state.velocity);
+            player_state.position = rules.integrator.integrate(
+                player_state.position,
+                player_state.velocity,
+                previous_velocity,
+                delta_time.as_secs_f32(),
+            );
 
             // Apply bounds
-            player_state.position.x = player_state
-                .position
-                .x
-                .clamp(rules.map_bounds.0, rules.map_bounds.1);
-            player_state.position.y = player_state
-                .position
-                .y
-                .clamp(rules.map_bounds.0, rules.map_bounds.1);
+            apply_bounds(
+                &mut player_state.position.x,
+                &mut player_state.velocity.x,
+                &rules,
+            );
+            apply_bounds(
+                &mut player_state.position.y,
+                &mut player_state.velocity.y,
+                &rules,
+            );
 
             // Clamp velocity
-            player_state.velocity.x = player_state
-                .velocity
-                .x
-                .clamp(-rules.max_velocity, rules.max_velocity);
-            player_state.velocity.y = player_state
-                .velocity
-                .y
-                .clamp(-rules.max_velocity, rules.max_velocity);
+            let max_speed = player_state.max_speed.unwrap_or(rules.max_velocity);
+            player_state.velocity.x = player_state.velocity.x.clamp(-max_speed, max_speed);
+            player_state.velocity.y = player_state.velocity.y.clamp(-max_speed, max_speed);
 
             player_state.last_update = Timestamp::now();
         }
 
+        self.tick_spawn_grace(delta_time);
+
         // Second pass: Check and resolve collisions
         self.resolve_collisions(&rules);
+        self.eliminate_out_of_bounds(&rules);
 
         self.last_update = Timestamp::now();
+        // Move, ReportPosition and apply_impulse drop non-finite input, so clients
+        // can't reach a broken state through them
+        debug_assert!(
+            self.check_invariants().is_ok(),
+            "{}",
+            self.check_invariants().unwrap_err()
+        );
         Ok(())
     }
 
+    // Checks what update() should always leave true: finite positions and velocities
+    // within the map and speed caps, and no per-player data for players not in the
+    // game. Collisions may push a player up to collision_radius past the wall and hand
+    // it another player's velocity, so those are allowed for
+    pub fn check_invariants(&self) -> std::result::Result<(), String> {
+        let (min, max) = self.rules.map_bounds;
+        let margin = self.rules.collision_radius.max(0.0);
+        let fastest = self
+            .players
+            .values()
+            .filter_map(|state| state.max_speed)
+            .fold(self.rules.max_velocity, f32::max);
+
+        for (player_id, state) in &self.players {
+            let (position, velocity) = (state.position, state.velocity);
+            if !(position.x.is_finite() && position.y.is_finite()) {
+                return Err(format!(
+                    "{} has a non-finite position {:?}",
+                    player_id, position
+                ));
+            }
+            if !(velocity.x.is_finite() && velocity.y.is_finite()) {
+                return Err(format!(
+                    "{} has a non-finite velocity {:?}",
+                    player_id, velocity
+                ));
+            }
+            let outside = |value: f32| value < min - margin || value > max + margin;
+            if outside(position.x) || outside(position.y) {
+                return Err(format!(
+                    "{} is outside the map at {:?}",
+                    player_id, position
+                ));
+            }
+            // Client-authoritative players aren't simulated, so nothing caps them
+            if !state.client_authoritative
+                && (velocity.x.abs() > fastest || velocity.y.abs() > fastest)
+            {
+                return Err(format!(
+                    "{} exceeds the speed cap at {:?}",
+                    player_id, velocity
+                ));
+            }
+        }
+
+        let stray = self
+            .previous_velocities
+            .keys()
+            .chain(self.spawn_grace.keys())
+            .chain(self.active_input.keys())
+            .find(|player_id| !self.players.contains_key(*player_id));
+        match stray {
+            Some(player_id) => Err(format!("State kept for removed player {}", player_id)),
+            None => Ok(()),
+        }
+    }
+
+    // Removes players outside the map in Eliminate mode, after collisions so players
+    // knocked out count too
+    fn eliminate_out_of_bounds(&mut self, rules: &GameRules) {
+        self.eliminated.clear();
+        if rules.boundary_mode != BoundaryMode::Eliminate {
+            return;
+        }
+        let (min, max) = rules.map_bounds;
+        let outside = |value: f32| value < min || value > max;
+        let mut eliminated: Vec<String> = self
+            .players
+            .iter()
+            .filter(|(_, state)| outside(state.position.x) || outside(state.position.y))
+            .map(|(id, _)| id.clone())
+            .collect();
+        eliminated.sort();
+        for player_id in &eliminated {
+            self.remove_player(player_id);
+        }
+        self.eliminated = eliminated;
+    }
+
+    // Marks the player as giving input, holding off stop decay for the input timeout
+    pub fn note_input(&mut self, player_id: &str) {
+        if let Some(timeout) = self.rules.input_timeout {
+            if self.players.contains_key(player_id) {
+                self.active_input.insert(player_id.to_string(), timeout);
+            }
+        }
+    }
+
+    // Whether input from the player arrived within the input timeout; always false
+    // when the rules have none
+    pub fn has_active_input(&self, player_id: &str) -> bool {
+        self.active_input.contains_key(player_id)
+    }
+
+    // Counts down active input and slows players whose input has stopped
+    fn decay_idle_velocities(&mut self, delta_time: Duration, rules: &GameRules) {
+        if rules.input_timeout.is_none() {
+            return;
+        }
+        self.active_input.retain(|_, remaining| {
+            *remaining = remaining.saturating_sub(delta_time);
+            !remaining.is_zero()
+        });
+
+        let slowdown = rules.stop_deceleration.max(0.0) * delta_time.as_secs_f32();
+        for (player_id, player) in self.players.iter_mut() {
+            if player.client_authoritative || self.active_input.contains_key(player_id) {
+                continue;
+            }
+            let speed = player.velocity.magnitude();
+            player.velocity = if speed <= slowdown {
+                Vector2::default()
+            } else {
+                player.velocity.scale((speed - slowdown) / speed)
+            };
+        }
+    }
+
+    // Counts down spawn protection, returning expired players to Active
+    fn tick_spawn_grace(&mut self, delta_time: Duration) {
+        let players = &mut self.players;
+        self.spawn_grace.retain(|player_id, remaining| {
+            *remaining = remaining.saturating_sub(delta_time);
+            if !remaining.is_zero() {
+                return true;
+            }
+            if let Some(player) = players.get_mut(player_id) {
+                player.status = PlayerStatus::Active;
+            }
+            false
+        });
+    }
+
     fn resolve_collisions(&mut self, rules: &GameRules) {
+        if !rules.enable_collisions {
+            self.collisions.clear();
+            return;
+        }
+
         // Collect current positions to avoid borrow checker issues
-        let positions: Vec<(String, Vector2)> = self
+        let mut positions: Vec<(String, Vector2)> = self
             .players
             .iter()
+            .filter(|(id, _)| !self.spawn_grace.contains_key(*id))
             .map(|(id, state)| (id.clone(), state.position))
             .collect();
+        // Sorted by id so pairs are found and resolved in the same order every run
+        positions.sort_by(|(a, _), (b, _)| a.cmp(b));
 
         // Track collisions that need to be resolved
         let mut collisions = Vec::new();
 
-        // Detect collisions
+        // Detect collisions, comparing squared distances to skip a sqrt per pair
+        let radius_squared = rules.collision_radius * rules.collision_radius;
         for i in 0..positions.len() {
             for j in (i + 1)..positions.len() {
                 let (id1, pos1) = &positions[i];
                 let (id2, pos2) = &positions[j];
 
-                let dx = pos1.x - pos2.x;
-                let dy = pos1.y - pos2.y;
-                let distance = (dx * dx + dy * dy).sqrt();
-
-                if distance < rules.collision_radius {
+                if pos1.distance_squared(pos2) < radius_squared {
                     collisions.push((id1.clone(), id2.clone()));
                 }
             }
         }
 
         // Resolve collisions
-        for (id1, id2) in collisions {
+        for (id1, id2) in &collisions {
             // First, collect the current states
-            let (pos1, pos2, vel1, vel2) = {
-                let player1 = &self.players[&id1];
-                let player2 = &self.players[&id2];
+            let (pos1, pos2, vel1, vel2, mass1, mass2) = {
+                let player1 = &self.players[id1];
+                let player2 = &self.players[id2];
                 (
                     player1.position,
                     player2.position,
                     player1.velocity,
                     player2.velocity,
+                    player1.mass,
+                    player2.mass,
                 )
             };
 
             // Calculate collision response
-            let dx = pos1.x - pos2.x;
-            let dy = pos1.y - pos2.y;
-            let distance = (dx * dx + dy * dy).sqrt();
+            let distance = pos1.distance(&pos2);
 
             let mut vel_updates = Vec::new();
             let mut pos_updates = Vec::new();
 
             if distance < rules.collision_radius {
                 // Calculate position updates
-                let overlap = rules.collision_radius - distance;
-                let angle = dy.atan2(dx);
-                let move_x = overlap * 0.5 * angle.cos();
-                let move_y = overlap * 0.5 * angle.sin();
+                let overlap = (rules.collision_radius - distance) * rules.separation_strength;
+                let offset = pos1.sub(&pos2);
+                let angle = offset.y.atan2(offset.x);
+                let (share1, share2) = push_shares(mass1, mass2);
+                let (move_x, move_y) = (overlap * angle.cos(), overlap * angle.sin());
 
                 // Store updates to apply later
                 vel_updates.push((id1.clone(), vel2));
@@ -153,15 +595,15 @@ impl GameState {
                 pos_updates.push((
                     id1.clone(),
                     Vector2 {
-                        x: pos1.x + move_x,
-                        y: pos1.y + move_y,
+                        x: pos1.x + move_x * share1,
+                        y: pos1.y + move_y * share1,
                     },
                 ));
                 pos_updates.push((
                     id2.clone(),
                     Vector2 {
-                        x: pos2.x - move_x,
-                        y: pos2.y - move_y,
+                        x: pos2.x - move_x * share2,
+                        y: pos2.y - move_y * share2,
                     },
                 ));
             }
@@ -179,14 +621,57 @@ impl GameState {
                 }
             }
         }
+
+        self.collisions = collisions;
+    }
+
+    pub fn last_collisions(&self) -> &[(String, String)] {
+        &self.collisions
+    }
+
+    // Players removed in the last update for leaving the arena, in id order
+    pub fn last_eliminated(&self) -> &[String] {
+        &self.eliminated
     }
 
     pub fn add_player(&mut self, player_id: String, state: PlayerState) {
         self.players.insert(player_id, state);
     }
 
+    // Adds a newly joined player under spawn protection for the rules' grace period
+    pub fn spawn_player(&mut self, player_id: String, mut state: PlayerState) {
+        if !self.rules.spawn_grace.is_zero() {
+            state.status = PlayerStatus::SpawnProtected;
+            self.spawn_grace
+                .insert(player_id.clone(), self.rules.spawn_grace);
+        }
+        self.players.insert(player_id, state);
+    }
+
     pub fn remove_player(&mut self, player_id: &str) {
         self.players.remove(player_id);
+        self.previous_velocities.remove(player_id);
+        self.spawn_grace.remove(player_id);
+        self.active_input.remove(player_id);
+    }
+
+    // Moves a player and their timers to a new id, e.g. a reconnect on a new
+    // connection. False if there is no such player
+    pub fn transfer_player(&mut self, from: &str, to: &str) -> bool {
+        let Some(state) = self.players.remove(from) else {
+            return false;
+        };
+        self.players.insert(to.to_string(), state);
+        if let Some(velocity) = self.previous_velocities.remove(from) {
+            self.previous_velocities.insert(to.to_string(), velocity);
+        }
+        if let Some(grace) = self.spawn_grace.remove(from) {
+            self.spawn_grace.insert(to.to_string(), grace);
+        }
+        if let Some(input) = self.active_input.remove(from) {
+            self.active_input.insert(to.to_string(), input);
+        }
+        true
     }
 
     pub fn get_player_state(&self, player_id: &str) -> Option<&PlayerState> {
@@ -196,300 +681,4514 @@ impl GameState {
     pub fn get_player_count(&self) -> usize {
         self.players.len()
     }
-}
-
-pub struct GameServer {
-    game_state: GameState,
-    players: HashMap<String, Player>,
-    tick_rate: Duration,
-    input_receiver: Receiver<InternalMessage>,
-    input_sender: Sender<InternalMessage>,
-    address: String,
-}
-
-impl GameServer {
-    pub fn new(address: &str) -> Result<Self> {
-        let (input_sender, input_receiver) = std::sync::mpsc::channel();
 
-        Ok(Self {
-            game_state: GameState::new(),
-            players: HashMap::new(),
-            tick_rate: Duration::from_millis(16),
-            input_receiver,
-            input_sender,
-            address: address.to_string(),
-        })
-    }
+    // Pushes a player (explosions, knockback): velocity changes by impulse / mass,
+    // clamped like any other velocity. Infinite mass players don't move.
+    pub fn apply_impulse(&mut self, player_id: &str, impulse: Vector2) -> Result<()> {
+        let max_velocity = self.rules.max_velocity;
+        let player = self
+            .players
+            .get_mut(player_id)
+            .ok_or_else(|| GameError::GameError(format!("Player {} has not joined", player_id)))?;
+        if !impulse.is_finite() {
+            return Err(GameError::GameError(format!(
+                "Impulse {:?} is not finite",
+                impulse
+            )));
+        }
 
-    pub fn add_connection(&mut self, player_id: String, sender: Sender<Vec<u8>>) {
-        let player = Player {
-            sender,
-            input_queue: VecDeque::new(),
-            state: PlayerState {
-                position: Vector2::default(),
-                velocity: Vector2::default(),
-                last_update: Timestamp::now(),
-            },
+        let velocity = player.velocity.add(&impulse.scale(1.0 / player.mass));
+        let max_speed = player.max_speed.unwrap_or(max_velocity);
+        player.velocity = Vector2 {
+            x: velocity.x.clamp(-max_speed, max_speed),
+            y: velocity.y.clamp(-max_speed, max_speed),
         };
-        self.players.insert(player_id, player);
+        Ok(())
     }
 
-    pub fn run(&mut self) -> Result<()> {
-        println!("Game server starting on {}", self.address);
-
-        let input_sender = self.input_sender.clone();
-        let address = self.address.clone();
-
-        // Spawn network handling thread
-        std::thread::spawn(move || {
-            if let Err(e) = super::network::handle_connections(&address, input_sender) {
-                eprintln!("Network error: {}", e);
-            }
-        });
-
-        self.run_game_loop()
+    // Lets the player's client report its position (e.g. client-simulated projectiles)
+    // instead of the server simulating it
+    pub fn set_client_authoritative(&mut self, player_id: &str, enabled: bool) -> Result<()> {
+        let player = self
+            .players
+            .get_mut(player_id)
+            .ok_or_else(|| GameError::GameError(format!("Player {} has not joined", player_id)))?;
+        player.client_authoritative = enabled;
+        Ok(())
     }
 
-    fn run_game_loop(&mut self) -> Result<()> {
-        let mut current_tick: u64 = 0;
-        let mut last_tick = Timestamp::now();
-
-        loop {
-            let now = Timestamp::now();
-            let delta_time = now.duration_since(&last_tick);
-
-            if delta_time >= self.tick_rate {
-                self.process_messages()?;
-                self.update_game_state(delta_time)?;
-                self.broadcast_state(current_tick)?;
-
-                current_tick += 1;
-                last_tick = now;
-            } else {
-                std::thread::sleep(self.tick_rate.saturating_sub(delta_time));
-            }
+    // Accepts a reported position for a client-authoritative player, clamped to the map.
+    // Returns false if the report was ignored, including when the player couldn't have
+    // covered the distance at its max speed since the last accepted report
+    fn report_position(&mut self, player_id: &str, position: Vector2) -> bool {
+        let (min, max) = self.rules.map_bounds;
+        let max_velocity = self.rules.max_velocity;
+        let Some(player) = self.players.get_mut(player_id) else {
+            return false;
+        };
+        if !player.client_authoritative || !position.is_finite() {
+            return false;
         }
-    }
+        let position = Vector2 {
+            x: position.x.clamp(min, max),
+            y: position.y.clamp(min, max),
+        };
 
-    fn process_messages(&mut self) -> Result<()> {
-        while let Ok(message) = self.input_receiver.try_recv() {
-            match message {
-                InternalMessage::NewConnection { player_id, sender } => {
-                    let player = Player {
-                        sender,
-                        input_queue: VecDeque::new(),
-                        state: PlayerState {
-                            position: Vector2::default(),
-                            velocity: Vector2::default(),
-                            last_update: Timestamp::now(),
-                        },
-                    };
-                    self.players.insert(player_id, player);
-                }
-                InternalMessage::ClientMessage { player_id, message } => {
-                    self.handle_client_message(&player_id, message)?;
-                }
-            }
+        let now = Timestamp::now();
+        let elapsed = now.duration_since(&player.last_update) + REPORT_TIME_SLACK;
+        // Speed is capped per axis, so diagonal travel reaches √2 times the cap
+        let max_speed = player.max_speed.unwrap_or(max_velocity) * std::f32::consts::SQRT_2;
+        if player.position.distance(&position) > max_speed * elapsed.as_secs_f32() {
+            return false;
         }
-        Ok(())
+        player.position = position;
+        player.last_update = now;
+        true
     }
 
-    fn handle_client_message(&mut self, player_id: &str, message: ClientMessage) -> Result<()> {
-        match message {
-            ClientMessage::Join => {
-                self.handle_player_join(player_id)?;
-            }
-            ClientMessage::Move { direction } => {
-                if let Some(player) = self.game_state.players.get_mut(player_id) {
-                    player.velocity = direction;
-                    player.last_update = Timestamp::now();
-                }
-            }
-            ClientMessage::Disconnect => {
-                self.remove_player(player_id)?;
-            }
+    // Sets a metadata entry within the rules' key count and size limits
+    pub fn set_metadata(&mut self, player_id: &str, key: String, value: String) -> Result<()> {
+        let max_keys = self.rules.max_metadata_keys;
+        let max_len = self.rules.max_metadata_len;
+        let player = self
+            .players
+            .get_mut(player_id)
+            .ok_or_else(|| GameError::GameError(format!("Player {} has not joined", player_id)))?;
+
+        if key.is_empty() || key.len() > max_len || value.len() > max_len {
+            return Err(GameError::GameError(format!(
+                "Metadata keys and values must be 1-{} bytes",
+                max_len
+            )));
         }
+        if !player.metadata.contains_key(&key) && player.metadata.len() >= max_keys {
+            return Err(GameError::GameError(format!(
+                "At most {} metadata keys allowed",
+                max_keys
+            )));
+        }
+
+        player.metadata.insert(key, value);
         Ok(())
     }
 
-    fn handle_player_join(&mut self, player_id: &str) -> Result<()> {
-        println!("Player {} joining", player_id);
+    // The name `player_id` would end up with under the rules' name policy
+    pub fn claim_name(&self, player_id: &str, name: &str) -> Result<String> {
+        self.claim_name_replacing(player_id, name, None)
+    }
 
-        // Create the player state
-        let player_state = PlayerState {
-            position: Vector2::default(),
-            velocity: Vector2::default(),
-            last_update: Timestamp::now(),
+    // Like claim_name, also ignoring the name of `replacing`, a player about to be
+    // removed in favour of this one
+    pub(super) fn claim_name_replacing(
+        &self,
+        player_id: &str,
+        name: &str,
+        replacing: Option<&str>,
+    ) -> Result<String> {
+        let taken = |candidate: &str| {
+            self.players.iter().any(|(id, state)| {
+                id != player_id
+                    && Some(id.as_str()) != replacing
+                    && state.name.as_deref() == Some(candidate)
+            })
         };
-
-        // Add to game state
-        self.game_state
-            .players
-            .insert(player_id.to_string(), player_state);
-
-        // Send join confirmation if we have their sender
-        if let Some(player) = self.players.get(player_id) {
-            let join_message = ServerMessage::JoinAccepted {
-                player_id: player_id.to_string(),
-            };
-            let json = serde_json::to_string(&join_message)?;
-            player
-                .sender
-                .send(format!("{}\n", json).into_bytes())
-                .map_err(|_| GameError::NetworkError("Failed to send join confirmation".into()))?;
+        if self.rules.name_policy == NamePolicy::Allow || !taken(name) {
+            return Ok(name.to_string());
+        }
+        if self.rules.name_policy == NamePolicy::Reject {
+            return Err(GameError::GameError(format!("Name {} is taken", name)));
         }
 
-        Ok(())
+        (2..)
+            .map(|n| {
+                // Trim the base so the suffixed name still fits
+                let suffix = format!(" {}", n);
+                let base: String = name
+                    .chars()
+                    .take(MAX_NAME_LEN.saturating_sub(suffix.len()))
+                    .collect();
+                base.trim_end().to_string() + &suffix
+            })
+            .find(|candidate| !taken(candidate))
+            .ok_or_else(|| GameError::GameError("No free name suffix".into()))
     }
 
-    fn update_game_state(&mut self, delta_time: Duration) -> Result<()> {
-        self.game_state.update(delta_time)
+    pub fn player_ids(&self) -> impl Iterator<Item = &String> {
+        self.players.keys()
     }
 
-    fn broadcast_state(&mut self, tick: u64) -> Result<()> {
-        let update = ServerMessage::GameState(GameStateUpdate {
-            tick,
-            players: self.game_state.players.clone(),
-            server_time: Timestamp::now(),
-        });
+    pub fn players(&self) -> &HashMap<String, PlayerState> {
+        &self.players
+    }
 
-        let message =
-            serde_json::to_string(&update).map(|json| format!("{}\n", json).into_bytes())?;
+    // Sum of 0.5 * m * |v|^2, for physics debugging. Immovable (infinite mass) players are skipped
+    pub fn total_kinetic_energy(&self) -> f32 {
+        self.players
+            .values()
+            .filter(|state| state.mass.is_finite())
+            .map(|state| {
+                let speed_squared = state.velocity.distance_squared(&Vector2::default());
+                0.5 * state.mass * speed_squared
+            })
+            .sum()
+    }
 
-        let mut disconnected_players = Vec::new();
+    // Players whose position is within `radius` of `center`, boundary inclusive, ordered by id
+    pub fn players_within(&self, center: Vector2, radius: f32) -> Vec<(String, &PlayerState)> {
+        let radius_squared = radius * radius;
+        let mut nearby: Vec<(String, &PlayerState)> = self
+            .players
+            .iter()
+            .filter(|(_, state)| state.position.distance_squared(&center) <= radius_squared)
+            .map(|(id, state)| (id.clone(), state))
+            .collect();
+        nearby.sort_by(|(a, _), (b, _)| a.cmp(b));
+        nearby
+    }
 
-        for (player_id, player) in &self.players {
-            if player.sender.send(message.clone()).is_err() {
-                disconnected_players.push(player_id.clone());
-            }
-        }
+    // Closest player to `to` and its distance, skipping `exclude` (usually the asker).
+    // Equidistant players are broken by the lowest id
+    pub fn nearest_player(&self, to: Vector2, exclude: Option<&str>) -> Option<(String, f32)> {
+        self.players
+            .iter()
+            .filter(|(id, _)| Some(id.as_str()) != exclude)
+            .map(|(id, state)| (id, state.position.distance_squared(&to)))
+            .min_by(|(id_a, a), (id_b, b)| a.total_cmp(b).then_with(|| id_a.cmp(id_b)))
+            .map(|(id, distance_squared)| (id.clone(), distance_squared.sqrt()))
+    }
+}
 
-        // Clean up disconnected players
-        for player_id in disconnected_players {
-            self.remove_player(&player_id)?;
+// A connection must Join (or Spectate) before anything that acts on a player, and may
+// only Join once. Ping, Reliable and Batch wrappers and Disconnect are valid at any
+// point; the messages they wrap are checked on their own
+fn order_violation(message: &ClientMessage, joined: bool) -> Option<&'static str> {
+    match message {
+        ClientMessage::Join { .. } if joined => Some("Already joined"),
+        ClientMessage::Spectate if joined => Some("Joined players cannot spectate"),
+        ClientMessage::Move { .. }
+        | ClientMessage::ReportPosition { .. }
+        | ClientMessage::SetMeta { .. }
+        | ClientMessage::SetName { .. }
+        | ClientMessage::Chat { .. }
+            if !joined =>
+        {
+            Some("Join before sending player messages")
         }
+        _ => None,
+    }
+}
 
-        Ok(())
+// Chunks of at most `chunk_size` players, at least one so an empty game still gets a
+// snapshot
+fn snapshot_parts<T>(players: &[T], chunk_size: usize) -> Vec<&[T]> {
+    if players.is_empty() {
+        return vec![players];
     }
+    players.chunks(chunk_size).collect()
+}
 
-    fn remove_player(&mut self, player_id: &str) -> Result<()> {
-        self.players.remove(player_id);
-        self.game_state.players.remove(player_id);
-        println!("Player {} disconnected", player_id);
-        Ok(())
+// Fraction of a collision's separation each player moves, lighter players move more
+fn push_shares(mass1: f32, mass2: f32) -> (f32, f32) {
+    match (mass1.is_infinite(), mass2.is_infinite()) {
+        (true, false) => (0.0, 1.0),
+        (false, true) => (1.0, 0.0),
+        _ if mass1 + mass2 > 0.0 && (mass1 + mass2).is_finite() => {
+            (mass2 / (mass1 + mass2), mass1 / (mass1 + mass2))
+        }
+        _ => (0.5, 0.5),
     }
 }
 
-// ----------- TESTS ---------
+// Whether clients would see no difference between two snapshots (ignores last_update)
+fn same_visible_state(a: &HashMap<String, PlayerState>, b: &HashMap<String, PlayerState>) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|(id, before)| b.get(id).is_some_and(|after| before.visible_eq(after)))
+}
+
+// Clamps one axis to the map and adjusts velocity into the wall per boundary mode
+fn apply_bounds(position: &mut f32, velocity: &mut f32, rules: &GameRules) {
+    // Left free to cross the wall; eliminated after the update instead
+    if rules.boundary_mode == BoundaryMode::Eliminate {
+        return;
+    }
+    let (min, max) = rules.map_bounds;
+    let into_wall = (*position <= min && *velocity < 0.0) || (*position >= max && *velocity > 0.0);
+    *position = position.clamp(min, max);
+
+    if into_wall {
+        *velocity = match rules.boundary_mode {
+            BoundaryMode::Clamp => 0.0,
+            BoundaryMode::Bounce { restitution } => -*velocity * restitution,
+            BoundaryMode::Eliminate => *velocity,
+        };
+    }
+}
+
+// Called once per tick after the simulation step, with the tick number
+pub type TickHook = Box<dyn FnMut(&mut GameState, u64) + Send>;
+
+// What a join validator sees about a connection asking to join
+#[derive(Clone, Debug)]
+pub struct JoinRequest<'a> {
+    pub player_id: &'a str,
+    // Peer address of the connection, when the id is one (it is for TCP connections)
+    pub addr: Option<SocketAddr>,
+    pub name: Option<&'a str>,
+    pub token: Option<&'a str>,
+}
+
+// Why a join was refused, sent back as an Error
+type Rejection = (ErrorCode, String);
+
+// Admits a join with Ok, or rejects it with a reason sent back to the client
+pub type JoinValidator = Box<dyn Fn(&JoinRequest) -> std::result::Result<(), String> + Send>;
+
+// A phase taking more than this fraction of the tick budget is logged
+const DEFAULT_SLOW_PHASE_FRACTION: f32 = 0.5;
+
+const DEFAULT_TICK_RATE: Duration = Duration::from_millis(16);
+
+// Spectators get full snapshots at 2Hz by default
+const DEFAULT_SPECTATOR_RATE: Duration = Duration::from_millis(500);
+
+pub struct GameServer {
+    game_state: GameState,
+    players: HashMap<String, Player>,
+    tick_rate: Duration,
+    input_receiver: Receiver<InternalMessage>,
+    input_sender: Sender<InternalMessage>,
+    // Every address served, resolved, in the order of `listeners`
+    addresses: Vec<String>,
+    // Bound by build(), then handed to the network thread when it starts
+    listeners: Vec<TcpListener>,
+    network_config: NetworkConfig,
+    network_started: bool,
+    current_tick: u64,
+    event_log: EventLog,
+    max_players_per_update: Option<usize>,
+    on_tick: Option<TickHook>,
+    join_validator: Option<JoinValidator>,
+    slow_phase_fraction: Option<f32>,
+    init_data: Option<Vec<u8>>,
+    stats: ServerStats,
+    // Idle ticks between NoChange keepalives; None always sends full snapshots
+    idle_keepalive_ticks: Option<u64>,
+    // Ticks between full keyframes when sending deltas; None always sends full snapshots
+    delta_keyframe_ticks: Option<u64>,
+    // Players and tick of the last snapshot or delta sent, the base for the next delta
+    last_broadcast_players: Option<HashMap<String, PlayerState>>,
+    last_state_tick: u64,
+    last_keyframe_tick: u64,
+    // Last tick anything state-related was sent, for keepalives
+    last_broadcast_tick: u64,
+    // Connections watching without a player; they skip per-tick state
+    spectators: HashSet<String>,
+    spectator_rate: Duration,
+    spectator_accumulator: Duration,
+    // Joined players' slots; snapshots are keyed by slot when slot_encoding is on
+    slots: SlotTable,
+    slot_encoding: bool,
+    // Buffer moves each tick and apply them in client_time order
+    input_ordering: bool,
+    // Server minus client clock in ms, from each player's first timestamped input
+    clock_skew: HashMap<String, i64>,
+    // Identity token to the connection that joined with it
+    identities: HashMap<String, String>,
+    // Decimal places floats in broadcasts are rounded to, full precision when None
+    float_precision: Option<u32>,
+    // PlayerState fields included in full snapshots, every field when None
+    snapshot_fields: Option<HashSet<SnapshotField>>,
+    // Full snapshots list players as id-sorted pairs instead of a map
+    sorted_players: bool,
+    // Serializes and sends per-tick state off the simulation thread when set
+    broadcaster: Option<Broadcaster>,
+    // This tick's state messages, waiting for flush_state
+    pending_state: Vec<(ServerMessage, Audience)>,
+    // Server-driven players, steered in id order so seeded games replay exactly
+    bots: BTreeMap<String, Bot>,
+    next_bot_id: u32,
+    position_history: PositionHistory,
+    // Disconnected players kept for the reconnect grace period, with when they left
+    retained: HashMap<String, Timestamp>,
+}
+
+// Who a broadcast goes to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Audience {
+    Everyone,
+    Players,
+    Spectators,
+}
+
+// Collects GameServer options; build() validates them and creates the server
+pub struct GameServerBuilder {
+    address: String,
+    extra_addresses: Vec<String>,
+    listeners: Vec<TcpListener>,
+    rules: GameRules,
+    tick_rate: Duration,
+    network_config: NetworkConfig,
+    max_players_per_update: Option<usize>,
+    on_tick: Option<TickHook>,
+    join_validator: Option<JoinValidator>,
+    slow_phase_fraction: Option<f32>,
+    init_data: Option<Vec<u8>>,
+    idle_keepalive_ticks: Option<u64>,
+    delta_keyframe_ticks: Option<u64>,
+    spectator_rate: Duration,
+    slot_encoding: bool,
+    input_ordering: bool,
+    float_precision: Option<u32>,
+    snapshot_fields: Option<HashSet<SnapshotField>>,
+    sorted_players: bool,
+    broadcast_thread: bool,
+}
+
+impl GameServerBuilder {
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            extra_addresses: Vec::new(),
+            listeners: Vec::new(),
+            rules: GameRules::default(),
+            tick_rate: DEFAULT_TICK_RATE,
+            network_config: NetworkConfig::default(),
+            max_players_per_update: None,
+            on_tick: None,
+            join_validator: None,
+            slow_phase_fraction: Some(DEFAULT_SLOW_PHASE_FRACTION),
+            init_data: None,
+            idle_keepalive_ticks: None,
+            delta_keyframe_ticks: None,
+            spectator_rate: DEFAULT_SPECTATOR_RATE,
+            slot_encoding: false,
+            input_ordering: false,
+            float_precision: None,
+            snapshot_fields: None,
+            sorted_players: false,
+            broadcast_thread: false,
+        }
+    }
+
+    // Serves on an already-bound listener instead of binding the address. Call again
+    // to serve on several
+    pub fn listener(mut self, listener: TcpListener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    // Also listens on `address`, e.g. IPv6 or a LAN interface next to localhost.
+    // Connections on every address join the same game
+    pub fn bind(mut self, address: &str) -> Self {
+        self.extra_addresses.push(address.to_string());
+        self
+    }
+
+    // Replaces all rules, so call before max_players
+    pub fn rules(mut self, rules: GameRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn max_players(mut self, max_players: Option<usize>) -> Self {
+        self.rules.max_players = max_players;
+        self
+    }
+
+    pub fn tick_rate(mut self, tick_rate: Duration) -> Self {
+        self.tick_rate = tick_rate;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.network_config.write_timeout = timeout;
+        self
+    }
+
+    pub fn read_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.network_config.read_deadline = deadline;
+        self
+    }
+
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.network_config.max_connections = max_connections;
+        self
+    }
+
+    pub fn max_players_per_update(mut self, max_players: Option<usize>) -> Self {
+        self.max_players_per_update = max_players;
+        self
+    }
+
+    pub fn on_tick(mut self, hook: impl FnMut(&mut GameState, u64) + Send + 'static) -> Self {
+        self.on_tick = Some(Box::new(hook));
+        self
+    }
+
+    pub fn join_validator(
+        mut self,
+        validator: impl Fn(&JoinRequest) -> std::result::Result<(), String> + Send + 'static,
+    ) -> Self {
+        self.join_validator = Some(Box::new(validator));
+        self
+    }
+
+    pub fn slow_phase_fraction(mut self, fraction: Option<f32>) -> Self {
+        self.slow_phase_fraction = fraction;
+        self
+    }
+
+    pub fn init_data(mut self, payload: Option<Vec<u8>>) -> Self {
+        self.init_data = payload;
+        self
+    }
+
+    pub fn idle_suppression(mut self, keepalive_ticks: Option<u64>) -> Self {
+        self.idle_keepalive_ticks = keepalive_ticks;
+        self
+    }
+
+    pub fn delta_updates(mut self, keyframe_ticks: Option<u64>) -> Self {
+        self.delta_keyframe_ticks = keyframe_ticks;
+        self
+    }
+
+    pub fn spectator_rate(mut self, rate: Duration) -> Self {
+        self.spectator_rate = rate;
+        self
+    }
+
+    pub fn slot_encoding(mut self, enabled: bool) -> Self {
+        self.slot_encoding = enabled;
+        self
+    }
+
+    pub fn input_ordering(mut self, enabled: bool) -> Self {
+        self.input_ordering = enabled;
+        self
+    }
+
+    pub fn float_precision(mut self, decimals: Option<u32>) -> Self {
+        self.float_precision = decimals;
+        self
+    }
+
+    pub fn snapshot_fields(mut self, fields: Option<HashSet<SnapshotField>>) -> Self {
+        self.snapshot_fields = fields;
+        self
+    }
+
+    pub fn sorted_players(mut self, enabled: bool) -> Self {
+        self.sorted_players = enabled;
+        self
+    }
+
+    pub fn broadcast_thread(mut self, enabled: bool) -> Self {
+        self.broadcast_thread = enabled;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |message: &str| Err(GameError::GameError(message.into()));
+        if self.tick_rate.is_zero() {
+            return invalid("Tick rate must be non-zero");
+        }
+        if self.spectator_rate.is_zero() {
+            return invalid("Spectator rate must be non-zero");
+        }
+        if self.network_config.max_connections == 0 {
+            return invalid("Max connections must be at least 1");
+        }
+        self.rules.validate()
+    }
+
+    // Binds every address here, so one that is taken fails the build instead of the
+    // network thread
+    pub fn build(self) -> Result<GameServer> {
+        self.validate()?;
+        let mut listeners = self.listeners;
+        let mut to_bind = self.extra_addresses;
+        if listeners.is_empty() {
+            to_bind.insert(0, self.address);
+        }
+        for address in &to_bind {
+            let listener = TcpListener::bind(address.as_str()).map_err(|e| {
+                GameError::NetworkError(format!("Failed to bind {}: {}", address, e))
+            })?;
+            listeners.push(listener);
+        }
+        let addresses = listeners
+            .iter()
+            .map(|listener| Ok(listener.local_addr()?.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        let (input_sender, input_receiver) = std::sync::mpsc::channel();
+
+        Ok(GameServer {
+            position_history: PositionHistory::new(self.rules.history_ticks),
+            game_state: GameState::with_rules(self.rules),
+            players: HashMap::new(),
+            tick_rate: self.tick_rate,
+            input_receiver,
+            input_sender,
+            addresses,
+            listeners,
+            network_config: self.network_config,
+            network_started: false,
+            current_tick: 0,
+            event_log: EventLog::default(),
+            max_players_per_update: self.max_players_per_update,
+            on_tick: self.on_tick,
+            join_validator: self.join_validator,
+            slow_phase_fraction: self.slow_phase_fraction,
+            init_data: self.init_data,
+            stats: ServerStats::default(),
+            idle_keepalive_ticks: self.idle_keepalive_ticks,
+            delta_keyframe_ticks: self.delta_keyframe_ticks,
+            last_broadcast_players: None,
+            last_state_tick: 0,
+            last_keyframe_tick: 0,
+            last_broadcast_tick: 0,
+            spectators: HashSet::new(),
+            spectator_rate: self.spectator_rate,
+            spectator_accumulator: Duration::ZERO,
+            slots: SlotTable::default(),
+            slot_encoding: self.slot_encoding,
+            input_ordering: self.input_ordering,
+            clock_skew: HashMap::new(),
+            identities: HashMap::new(),
+            retained: HashMap::new(),
+            float_precision: self.float_precision,
+            snapshot_fields: self.snapshot_fields,
+            sorted_players: self.sorted_players,
+            broadcaster: self.broadcast_thread.then(Broadcaster::spawn),
+            pending_state: Vec::new(),
+            bots: BTreeMap::new(),
+            next_bot_id: 1,
+        })
+    }
+}
+
+impl GameServer {
+    pub fn new(address: &str) -> Result<Self> {
+        GameServerBuilder::new(address).build()
+    }
+
+    pub fn with_rules(address: &str, rules: GameRules) -> Result<Self> {
+        GameServerBuilder::new(address).rules(rules).build()
+    }
+
+    // Serves on an already-bound listener, e.g. port 0 in tests or socket activation
+    pub fn with_listener(listener: TcpListener, rules: GameRules) -> Result<Self> {
+        GameServerBuilder::new("")
+            .listener(listener)
+            .rules(rules)
+            .build()
+    }
+
+    // The first address served; see local_addrs for servers on several
+    pub fn local_addr(&self) -> &str {
+        &self.addresses[0]
+    }
+
+    pub fn local_addrs(&self) -> &[String] {
+        &self.addresses
+    }
+
+    pub fn tick_rate(&self) -> Duration {
+        self.tick_rate
+    }
+
+    // Snapshots go out once per tick
+    pub fn broadcast_rate(&self) -> Duration {
+        self.tick_rate
+    }
+
+    pub fn spectator_rate(&self) -> Duration {
+        self.spectator_rate
+    }
+
+    pub fn set_spectator_rate(&mut self, rate: Duration) {
+        self.spectator_rate = rate;
+    }
+
+    // Client-relevant rules and cadence, sent to each player on join
+    pub fn world_info(&self) -> WorldInfo {
+        WorldInfo {
+            tick_rate_ms: self.tick_rate().as_millis() as u64,
+            broadcast_rate_ms: self.broadcast_rate().as_millis() as u64,
+            ..self.game_state.rules().world_info()
+        }
+    }
+
+    pub fn stats(&self) -> &ServerStats {
+        &self.stats
+    }
+
+    pub fn events_in(&self, range: impl RangeBounds<u64>) -> Vec<Event> {
+        self.event_log.in_range(range)
+    }
+
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.network_config.write_timeout = timeout;
+    }
+
+    // Snapshots with more players than this are split across several messages
+    pub fn set_max_players_per_update(&mut self, max_players: Option<usize>) {
+        self.max_players_per_update = max_players;
+    }
+
+    // Bytes sent to each player right after JoinAccepted, e.g. a map or serialized rules
+    pub fn set_init_data(&mut self, payload: Option<Vec<u8>>) {
+        self.init_data = payload;
+    }
+
+    // When set, unchanged ticks skip the snapshot and send NoChange every `keepalive_ticks`
+    pub fn set_idle_suppression(&mut self, keepalive_ticks: Option<u64>) {
+        self.idle_keepalive_ticks = keepalive_ticks;
+        self.last_broadcast_players = None;
+    }
+
+    // When set, ticks between full keyframes send only changed players and fields
+    pub fn set_delta_updates(&mut self, keyframe_ticks: Option<u64>) {
+        self.delta_keyframe_ticks = keyframe_ticks;
+        self.last_broadcast_players = None;
+    }
+
+    // Keys full snapshots by compact slot numbers instead of player ids. The id/slot
+    // mapping is sent on join and leave. Enable before players join
+    pub fn set_slot_encoding(&mut self, enabled: bool) {
+        self.slot_encoding = enabled;
+    }
+
+    // Buffers each tick's moves and applies them in client_time order rather than
+    // arrival order. Moves without client_time are ordered by arrival time
+    pub fn set_input_ordering(&mut self, enabled: bool) {
+        self.input_ordering = enabled;
+    }
+
+    // Rounds floats in broadcasts (positions, velocities) to `decimals` places to cut
+    // snapshot size when sub-pixel precision isn't needed
+    pub fn set_float_precision(&mut self, decimals: Option<u32>) {
+        self.float_precision = decimals;
+    }
+
+    // Limits full snapshots to the given PlayerState fields, e.g. positions only for a
+    // minimap. Deltas and slot snapshots always carry every field
+    pub fn set_snapshot_fields(&mut self, fields: Option<HashSet<SnapshotField>>) {
+        self.snapshot_fields = fields;
+    }
+
+    // Sends full snapshots' players as (id, state) pairs sorted by id, so the same
+    // state always serializes to the same bytes (golden tests, diffable logs)
+    pub fn set_sorted_players(&mut self, enabled: bool) {
+        self.sorted_players = enabled;
+    }
+
+    // Serializes and sends snapshots on a separate thread so a slow broadcast can't
+    // delay the next tick. If it falls behind, only the newest tick's state is sent,
+    // as a full snapshot. Broadcasts such as PlayerLeft go through the same thread to
+    // keep their order; replies to one connection (joins, errors) are still sent inline
+    pub fn set_broadcast_thread(&mut self, enabled: bool) {
+        if enabled == self.broadcaster.is_some() {
+            return;
+        }
+        self.pending_state.clear();
+        self.broadcaster = enabled.then(Broadcaster::spawn);
+    }
+
+    // Server minus client clock in ms, measured on the player's first timestamped input
+    pub fn clock_skew(&self, player_id: &str) -> Option<i64> {
+        self.clock_skew.get(player_id).copied()
+    }
+
+    pub fn slot_of(&self, player_id: &str) -> Option<u32> {
+        self.slots.get(player_id)
+    }
+
+    pub fn set_on_tick(&mut self, hook: impl FnMut(&mut GameState, u64) + Send + 'static) {
+        self.on_tick = Some(Box::new(hook));
+    }
+
+    // Runs before every Join (auth, allowlists...). A rejected connection gets a
+    // JoinRejected error with the reason and is closed
+    pub fn set_join_validator(
+        &mut self,
+        validator: impl Fn(&JoinRequest) -> std::result::Result<(), String> + Send + 'static,
+    ) {
+        self.join_validator = Some(Box::new(validator));
+    }
+
+    // Warn when one tick phase exceeds `fraction` of the tick rate; None disables
+    pub fn set_slow_phase_fraction(&mut self, fraction: Option<f32>) {
+        self.slow_phase_fraction = fraction;
+    }
+
+    // Connections that send no bytes for this long are dropped; None disables
+    pub fn set_read_deadline(&mut self, deadline: Option<Duration>) {
+        self.network_config.read_deadline = deadline;
+    }
+
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.network_config.max_connections = max_connections;
+    }
+
+    pub fn add_connection(&mut self, player_id: String, sender: Sender<Vec<u8>>) {
+        let rules = self.game_state.rules();
+        let player = Player {
+            sender,
+            input_queue: VecDeque::new(),
+            chat_bucket: TokenBucket::new(rules.chat_limit, rules.chat_interval),
+        };
+        self.players.insert(player_id, player);
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        self.run_for(u64::MAX)
+    }
+
+    // Handles a message as if `player_id` had sent it over the network, for tests and bots
+    pub fn inject(&mut self, player_id: &str, message: ClientMessage) -> Result<()> {
+        self.handle_client_message(player_id, message)
+    }
+
+    // Runs one tick with a fixed delta, without the network or wall clock
+    pub fn step(&mut self, delta_time: Duration) -> Result<()> {
+        self.run_tick(delta_time)
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+
+    // Where the player was at `tick`, for lag compensation such as hit checks against
+    // what a lagging client saw. Ticks older than GameRules::history_ticks are clamped
+    // to the oldest one kept. Returns the tick actually used with the position
+    pub fn rewind(&self, player_id: &str, tick: u64) -> Option<(u64, Vector2)> {
+        self.position_history.rewind(player_id, tick)
+    }
+
+    // Full per-player state for admin tooling, detached from the live game
+    pub fn players_snapshot(&self) -> HashMap<String, PlayerState> {
+        self.game_state.players.clone()
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    // Adds a player steered by the server each tick rather than by a connection.
+    // Bots show up in snapshots like anyone else, take a seat under max_players and
+    // are named under the name policy. Returns the bot's id
+    pub fn add_bot(&mut self, behavior: BotBehavior) -> Result<String> {
+        if let Some(max_players) = self.game_state.rules.max_players {
+            if self.game_state.get_player_count() >= max_players {
+                return Err(GameError::GameError(format!(
+                    "Server is full ({} players)",
+                    max_players
+                )));
+            }
+        }
+        let number = self.next_bot_id;
+        let bot_id = format!("bot-{}", number);
+        let name = self
+            .game_state
+            .claim_name(&bot_id, &format!("Bot {}", number))?;
+        self.next_bot_id += 1;
+
+        let player_state = PlayerState {
+            position: self.game_state.spawn_position(),
+            last_update: Timestamp::now(),
+            name: Some(name),
+            ..Default::default()
+        };
+        self.game_state.spawn_player(bot_id.clone(), player_state);
+        self.event_log.record(
+            self.current_tick,
+            EventKind::PlayerJoined {
+                player_id: bot_id.clone(),
+            },
+        );
+        self.bots.insert(bot_id.clone(), Bot::new(behavior));
+
+        if self.slot_encoding {
+            self.assign_slot(&bot_id)?;
+        }
+        println!("Bot {} added ({:?})", bot_id, behavior);
+        Ok(bot_id)
+    }
+
+    // Takes a bot out of the game, telling clients as for a player who left
+    pub fn remove_bot(&mut self, bot_id: &str) -> Result<()> {
+        if !self.bots.contains_key(bot_id) {
+            return Err(GameError::GameError(format!("No bot {}", bot_id)));
+        }
+        self.remove_player(bot_id)
+    }
+
+    // Tells one connection why it is being removed, then removes it
+    pub fn kick(&mut self, player_id: &str, reason: &str) -> Result<()> {
+        println!("Kicking {}: {}", player_id, reason);
+        self.send_error(player_id, ErrorCode::Kicked, reason.to_string());
+        self.remove_player(player_id)
+    }
+
+    // Removes every connection, players and spectators alike, and every bot, e.g.
+    // before a restart
+    pub fn disconnect_all(&mut self, reason: &str) -> Result<()> {
+        println!("Disconnecting everyone: {}", reason);
+        self.broadcast(&ServerMessage::Error {
+            error_code: ErrorCode::Kicked,
+            message: reason.to_string(),
+        })?;
+
+        let player_ids: Vec<String> = self
+            .players
+            .keys()
+            .chain(self.bots.keys())
+            .cloned()
+            .collect();
+        for player_id in player_ids {
+            self.remove_player(&player_id)?;
+        }
+        Ok(())
+    }
+
+    // Runs `ticks` simulation ticks and returns, for tests and benchmarks
+    pub fn run_for(&mut self, ticks: u64) -> Result<()> {
+        self.start_network();
+        self.run_game_loop(ticks)
+    }
+
+    fn start_network(&mut self) {
+        if self.network_started {
+            return;
+        }
+        self.network_started = true;
+
+        println!("Game server starting on {}", self.addresses.join(", "));
+
+        let input_sender = self.input_sender.clone();
+        let listeners = std::mem::take(&mut self.listeners);
+        let network_config = self.network_config.clone();
+
+        // Spawn network handling thread
+        std::thread::spawn(move || {
+            network::serve(listeners, input_sender, network_config);
+        });
+    }
+
+    fn run_game_loop(&mut self, ticks: u64) -> Result<()> {
+        if let Some(delta_time) = self.game_state.rules.headless_delta {
+            for _ in 0..ticks {
+                self.run_tick(delta_time)?;
+            }
+            return Ok(());
+        }
+
+        let mut ticks_run: u64 = 0;
+        let mut last_tick = Timestamp::now();
+
+        while ticks_run < ticks {
+            let now = Timestamp::now();
+            let delta_time = now.duration_since(&last_tick);
+
+            if delta_time >= self.tick_rate {
+                self.run_tick(delta_time)?;
+                ticks_run += 1;
+                last_tick = now;
+            } else {
+                std::thread::sleep(self.tick_rate.saturating_sub(delta_time));
+            }
+        }
+        Ok(())
+    }
+
+    fn run_tick(&mut self, delta_time: Duration) -> Result<()> {
+        let started = Instant::now();
+        self.process_messages()?;
+        self.reap_disconnected(&Timestamp::now())?;
+        self.check_phase("process", started.elapsed());
+
+        let started = Instant::now();
+        self.update_game_state(delta_time)?;
+        self.check_phase("update", started.elapsed());
+
+        let started = Instant::now();
+        self.broadcast_state(self.current_tick)?;
+        self.broadcast_spectator_state(delta_time)?;
+        self.flush_state();
+        self.check_phase("broadcast", started.elapsed());
+
+        self.current_tick += 1;
+        Ok(())
+    }
+
+    fn check_phase(&self, phase: &str, elapsed: Duration) {
+        let Some(fraction) = self.slow_phase_fraction else {
+            return;
+        };
+        let budget = self.tick_rate.mul_f32(fraction);
+        if elapsed > budget {
+            tracing::warn!(
+                tick = self.current_tick,
+                "Slow {} phase: {:?} (budget {:?})",
+                phase,
+                elapsed,
+                budget
+            );
+        }
+    }
+
+    fn process_messages(&mut self) -> Result<()> {
+        while let Ok(message) = self.input_receiver.try_recv() {
+            match message {
+                InternalMessage::NewConnection { player_id, sender } => {
+                    self.add_connection(player_id, sender);
+                }
+                InternalMessage::ClientMessage { player_id, message } => {
+                    self.handle_client_message(&player_id, message)?;
+                }
+            }
+        }
+        self.apply_queued_inputs();
+        Ok(())
+    }
+
+    fn handle_client_message(&mut self, player_id: &str, message: ClientMessage) -> Result<()> {
+        let joined = self.game_state.get_player_state(player_id).is_some();
+        if let Some(problem) = order_violation(&message, joined) {
+            println!("Rejecting message from {}: {}", player_id, problem);
+            self.send_error(player_id, ErrorCode::ProtocolError, problem.into());
+            return Ok(());
+        }
+
+        match message {
+            ClientMessage::Join { name, token } => {
+                // Checked before claiming the token, so a rejected join can't supersede
+                if let Err(reason) =
+                    self.validate_join(player_id, name.as_deref(), token.as_deref())
+                {
+                    println!("Rejecting join from {}: {}", player_id, reason);
+                    self.send_error(player_id, ErrorCode::JoinRejected, reason);
+                    return self.remove_player(player_id);
+                }
+                self.handle_player_join(player_id, name, token)?;
+            }
+            ClientMessage::Move {
+                direction,
+                client_time,
+                throttle,
+            } => {
+                let timestamp = self.input_time(player_id, client_time);
+                if !self.input_ordering {
+                    self.apply_move(player_id, direction, throttle);
+                } else {
+                    self.queue_input(
+                        player_id,
+                        PlayerInput {
+                            timestamp,
+                            message: ClientMessage::Move {
+                                direction,
+                                client_time,
+                                throttle,
+                            },
+                        },
+                    )?;
+                }
+            }
+            ClientMessage::ReportPosition { position } => {
+                if !self.game_state.report_position(player_id, position) {
+                    println!("Ignoring position report from {}", player_id);
+                }
+            }
+            ClientMessage::Ping { sent_at } => {
+                let pong = ServerMessage::Pong {
+                    sent_at,
+                    server_time: Timestamp::now(),
+                };
+                if let Err(e) = self.send_to_player(player_id, &pong) {
+                    eprintln!("Failed to send pong to {}: {}", player_id, e);
+                }
+            }
+            ClientMessage::SetMeta { key, value } => {
+                if let Err(e) = self.game_state.set_metadata(player_id, key, value) {
+                    self.send_error(player_id, ErrorCode::InvalidMetadata, e.to_string());
+                }
+            }
+            ClientMessage::SetName { name } => {
+                self.handle_rename(player_id, name)?;
+            }
+            ClientMessage::Chat { text } => {
+                self.handle_chat(player_id, text)?;
+            }
+            ClientMessage::Reliable { seq, message } => {
+                // Ack first so a reliable Disconnect is still acknowledged
+                if let Err(e) = self.send_to_player(player_id, &ServerMessage::Ack { seq }) {
+                    eprintln!("Failed to ack {} for {}: {}", seq, player_id, e);
+                }
+                self.handle_client_message(player_id, *message)?;
+            }
+            ClientMessage::Batch { messages } => {
+                for message in messages {
+                    // Stop if an earlier message (e.g. a Disconnect) removed the sender
+                    if !self.players.contains_key(player_id) {
+                        break;
+                    }
+                    self.handle_client_message(player_id, message)?;
+                }
+            }
+            ClientMessage::Spectate => {
+                self.handle_spectate(player_id)?;
+            }
+            ClientMessage::Disconnect => {
+                self.handle_disconnect(player_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs the join validator, if one is set. Err holds the reason to send back
+    fn validate_join(
+        &self,
+        player_id: &str,
+        name: Option<&str>,
+        token: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let Some(validator) = &self.join_validator else {
+            return Ok(());
+        };
+        validator(&JoinRequest {
+            player_id,
+            addr: player_id.parse().ok(),
+            name,
+            token,
+        })
+    }
+
+    // The player that joining with `token` would replace: a retained player holding
+    // it, or another live connection under DuplicatePolicy::Supersede. Err if the
+    // duplicate policy refuses this connection
+    fn identity_holder(
+        &self,
+        player_id: &str,
+        token: &str,
+    ) -> std::result::Result<Option<String>, Rejection> {
+        let Some(holder) = self.identities.get(token) else {
+            return Ok(None);
+        };
+        if self.retained.contains_key(holder) {
+            return Ok(Some(holder.clone()));
+        }
+        if holder == player_id || !self.players.contains_key(holder) {
+            return Ok(None);
+        }
+        match self.game_state.rules.duplicate_policy {
+            DuplicatePolicy::Reject => {
+                println!("Rejecting {}: identity held by {}", player_id, holder);
+                Err((
+                    ErrorCode::DuplicateConnection,
+                    "Identity is already connected".into(),
+                ))
+            }
+            DuplicatePolicy::Supersede => Ok(Some(holder.clone())),
+        }
+    }
+
+    fn apply_move(&mut self, player_id: &str, direction: Vector2, throttle: Option<f32>) {
+        let max_velocity = self.game_state.rules.max_velocity;
+        // Out of range numbers (e.g. 1e39) parse as infinite; treat them as a stop too
+        let direction = if direction.is_finite() {
+            direction
+        } else {
+            Vector2::default()
+        };
+        if let Some(player) = self.game_state.players.get_mut(player_id) {
+            player.velocity = match throttle {
+                Some(throttle) => {
+                    // A NaN throttle stops the player rather than poisoning its velocity
+                    let throttle = if throttle.is_nan() {
+                        0.0
+                    } else {
+                        throttle.clamp(0.0, 1.0)
+                    };
+                    let speed = player.max_speed.unwrap_or(max_velocity);
+                    direction.normalize().scale(speed * throttle)
+                }
+                None => direction,
+            };
+            player.last_update = Timestamp::now();
+        }
+        self.game_state.note_input(player_id);
+    }
+
+    // Maps a client timestamp onto the server clock using the skew seen on the player's
+    // first timestamped input; untimestamped inputs use their arrival time
+    fn input_time(&mut self, player_id: &str, client_time: Option<u64>) -> Timestamp {
+        let arrived = Timestamp::now();
+        let Some(client_time) = client_time else {
+            return arrived;
+        };
+        // Client times are untrusted, so the math is done wide enough not to overflow
+        let arrived_ms = arrived.as_millis() as i128;
+        let skew = *self
+            .clock_skew
+            .entry(player_id.to_string())
+            .or_insert_with(|| {
+                (arrived_ms - client_time as i128).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+            });
+        let drift = MAX_INPUT_TIME_DRIFT.as_millis() as i128;
+        let mapped =
+            (client_time as i128 + skew as i128).clamp(arrived_ms - drift, arrived_ms + drift);
+        Timestamp::from_millis(mapped.max(0) as u64)
+    }
+
+    // Buffers an input until the end of the tick, applying the overflow policy when full
+    fn queue_input(&mut self, player_id: &str, input: PlayerInput) -> Result<()> {
+        let rules = self.game_state.rules();
+        let (cap, policy) = (rules.max_input_queue, rules.input_overflow);
+        let Some(player) = self.players.get_mut(player_id) else {
+            return Ok(());
+        };
+
+        if player.input_queue.len() < cap {
+            player.input_queue.push_back(input);
+            return Ok(());
+        }
+        match policy {
+            OverflowPolicy::DropOldest => {
+                player.input_queue.pop_front();
+                if cap > 0 {
+                    player.input_queue.push_back(input);
+                }
+            }
+            OverflowPolicy::DropNewest => {}
+            OverflowPolicy::Disconnect => {
+                return self.kick(player_id, "Input queue overflow");
+            }
+        }
+        Ok(())
+    }
+
+    // Applies inputs buffered this tick in client-time order
+    fn apply_queued_inputs(&mut self) {
+        let mut queued = Vec::new();
+        for (player_id, player) in self.players.iter_mut() {
+            queued.extend(
+                player
+                    .input_queue
+                    .drain(..)
+                    .map(|input| (player_id.clone(), input)),
+            );
+        }
+        queued.sort_by_key(|(_, input)| input.timestamp.as_millis());
+
+        for (player_id, input) in queued {
+            if let ClientMessage::Move {
+                direction,
+                throttle,
+                ..
+            } = input.message
+            {
+                self.apply_move(&player_id, direction, throttle);
+            }
+        }
+    }
+
+    fn send_error(&self, player_id: &str, error_code: ErrorCode, message: String) {
+        let error = ServerMessage::Error {
+            error_code,
+            message,
+        };
+        if let Err(e) = self.send_to_player(player_id, &error) {
+            eprintln!("Failed to send error to {}: {}", player_id, e);
+        }
+    }
+
+    fn handle_rename(&mut self, player_id: &str, name: String) -> Result<()> {
+        let name =
+            match validate_name(&name).and_then(|_| self.game_state.claim_name(player_id, &name)) {
+                Ok(name) => name,
+                Err(e) => {
+                    self.send_error(player_id, ErrorCode::InvalidName, e.to_string());
+                    return Ok(());
+                }
+            };
+
+        let Some(player) = self.game_state.players.get_mut(player_id) else {
+            return Ok(());
+        };
+        player.name = Some(name.clone());
+        println!("Player {} renamed to {}", player_id, name);
+
+        self.broadcast(&ServerMessage::PlayerRenamed {
+            player_id: player_id.to_string(),
+            name,
+        })
+    }
+
+    fn handle_chat(&mut self, player_id: &str, text: String) -> Result<()> {
+        if text.trim().is_empty() || text.chars().count() > MAX_CHAT_LEN {
+            let message = format!("Chat must be 1-{} characters", MAX_CHAT_LEN);
+            self.send_error(player_id, ErrorCode::InvalidChat, message);
+            return Ok(());
+        }
+
+        let allowed = self
+            .players
+            .get_mut(player_id)
+            .is_some_and(|player| player.chat_bucket.try_take());
+        if !allowed {
+            self.send_error(
+                player_id,
+                ErrorCode::RateLimited,
+                "Sending chat too fast".into(),
+            );
+            return Ok(());
+        }
+
+        self.broadcast(&ServerMessage::ChatMessage {
+            player_id: player_id.to_string(),
+            text,
+        })
+    }
+
+    fn handle_spectate(&mut self, player_id: &str) -> Result<()> {
+        println!("Connection {} spectating", player_id);
+        self.spectators.insert(player_id.to_string());
+
+        let world_info = WorldInfo {
+            broadcast_rate_ms: self.spectator_rate.as_millis() as u64,
+            ..self.world_info()
+        };
+        self.send_to_player(player_id, &ServerMessage::WorldInfo(world_info))
+            .map_err(|_| GameError::NetworkError("Failed to send world info".into()))
+    }
+
+    fn send_to_player(&self, player_id: &str, message: &ServerMessage) -> Result<()> {
+        if let Some(player) = self.players.get(player_id) {
+            let json = serde_json::to_string(message)?;
+            player
+                .sender
+                .send(format!("{}\n", json).into_bytes())
+                .map_err(|_| GameError::NetworkError(format!("Failed to send to {}", player_id)))?;
+        }
+        Ok(())
+    }
+
+    fn handle_player_join(
+        &mut self,
+        player_id: &str,
+        name: Option<String>,
+        token: Option<String>,
+    ) -> Result<()> {
+        // Everything that can refuse the join runs before the old holder of
+        // the token is removed, so a failed join never costs a live player
+        let admitted = token
+            .as_deref()
+            .map_or(Ok(None), |token| self.identity_holder(player_id, token))
+            .and_then(|replacing| {
+                let name = self.check_join(player_id, name, replacing.as_deref())?;
+                Ok((name, replacing))
+            });
+        let (name, replacing) = match admitted {
+            Ok(admitted) => admitted,
+            Err((error_code, message)) => {
+                self.send_error(player_id, error_code, message);
+                return Ok(());
+            }
+        };
+        if let Some(token) = token {
+            self.identities.insert(token, player_id.to_string());
+        }
+        match replacing {
+            // The new connection picks up where the retained player left off
+            Some(holder) if self.retained.contains_key(&holder) => {
+                self.resume_retained(&holder, player_id, name)
+            }
+            Some(holder) => {
+                self.kick(&holder, "Superseded by a new connection")?;
+                self.spawn_joined(player_id, name)
+            }
+            None => self.spawn_joined(player_id, name),
+        }
+    }
+
+    // Moves a retained player, with their slot and history, onto the connection that
+    // rejoined with their token. A name given with the join replaces theirs
+    fn resume_retained(
+        &mut self,
+        retained_id: &str,
+        player_id: &str,
+        name: Option<String>,
+    ) -> Result<()> {
+        println!("Player {} resuming as {}", retained_id, player_id);
+        self.retained.remove(retained_id);
+        self.spectators.remove(player_id);
+        self.identities.retain(|_, holder| holder != retained_id);
+        self.game_state.transfer_player(retained_id, player_id);
+        self.position_history.transfer(retained_id, player_id);
+        self.slots.transfer(retained_id, player_id);
+
+        let name = match self.game_state.players.get_mut(player_id) {
+            Some(player) => {
+                if name.is_some() {
+                    player.name = name;
+                }
+                // Movement checks measure from now, not from the disconnect
+                player.last_update = Timestamp::now();
+                player.name.clone()
+            }
+            None => name,
+        };
+        self.welcome(player_id, name)?;
+        if self.slot_encoding {
+            self.assign_slot(player_id)?;
+        }
+        Ok(())
+    }
+
+    // Checks a join's name and the player cap. `replacing` is a player the join will
+    // remove, so its name and place don't count against it. Returns the name to join
+    // with, or why the join is refused
+    fn check_join(
+        &self,
+        player_id: &str,
+        name: Option<String>,
+        replacing: Option<&str>,
+    ) -> std::result::Result<Option<String>, Rejection> {
+        let name = match name {
+            Some(name) => Some(
+                validate_name(&name)
+                    .and_then(|_| {
+                        self.game_state
+                            .claim_name_replacing(player_id, &name, replacing)
+                    })
+                    .map_err(|e| {
+                        println!("Rejecting {}: {}", player_id, e);
+                        (ErrorCode::InvalidName, e.to_string())
+                    })?,
+            ),
+            None => None,
+        };
+
+        if let Some(max_players) = self.game_state.rules.max_players {
+            let freed = replacing.is_some_and(|id| self.game_state.get_player_state(id).is_some());
+            if self.game_state.get_player_count() - freed as usize >= max_players {
+                println!("Rejecting {}: server full", player_id);
+                return Err((
+                    ErrorCode::ServerFull,
+                    format!("Server is full ({} players)", max_players),
+                ));
+            }
+        }
+        Ok(name)
+    }
+
+    // Adds a player whose join passed check_join
+    fn spawn_joined(&mut self, player_id: &str, name: Option<String>) -> Result<()> {
+        println!("Player {} joining", player_id);
+        self.spectators.remove(player_id);
+
+        // Create the player state
+        let player_state = PlayerState {
+            position: self.game_state.spawn_position(),
+            velocity: Vector2::default(),
+            last_update: Timestamp::now(),
+            name: name.clone(),
+            ..Default::default()
+        };
+
+        // Add to game state
+        self.game_state
+            .spawn_player(player_id.to_string(), player_state);
+        self.event_log.record(
+            self.current_tick,
+            EventKind::PlayerJoined {
+                player_id: player_id.to_string(),
+            },
+        );
+
+        self.welcome(player_id, name)?;
+        if self.slot_encoding {
+            self.assign_slot(player_id)?;
+        }
+
+        Ok(())
+    }
+
+    // Confirms the join and sends what a player needs before their first snapshot
+    fn welcome(&mut self, player_id: &str, name: Option<String>) -> Result<()> {
+        // Send join confirmation if we have their sender
+        let join_message = ServerMessage::JoinAccepted {
+            player_id: player_id.to_string(),
+            name,
+        };
+        self.send_to_player(player_id, &join_message)
+            .map_err(|_| GameError::NetworkError("Failed to send join confirmation".into()))?;
+
+        let world_message = ServerMessage::WorldInfo(self.world_info());
+        self.send_to_player(player_id, &world_message)
+            .map_err(|_| GameError::NetworkError("Failed to send world info".into()))?;
+
+        if let Some(payload) = &self.init_data {
+            let init_message = ServerMessage::InitData {
+                payload: payload.clone(),
+            };
+            self.send_to_player(player_id, &init_message)
+                .map_err(|_| GameError::NetworkError("Failed to send init data".into()))?;
+        }
+        Ok(())
+    }
+
+    // Tells the new player every existing mapping, then everyone the new one
+    fn assign_slot(&mut self, player_id: &str) -> Result<()> {
+        // A resumed player already holds their slot; it is broadcast below
+        for (id, slot) in self.slots.iter().filter(|(id, _)| *id != player_id) {
+            let existing = ServerMessage::SlotAssigned {
+                slot,
+                player_id: id.clone(),
+            };
+            self.send_to_player(player_id, &existing)?;
+        }
+
+        let slot = self.slots.assign(player_id);
+        self.broadcast(&ServerMessage::SlotAssigned {
+            slot,
+            player_id: player_id.to_string(),
+        })
+    }
+
+    fn update_game_state(&mut self, delta_time: Duration) -> Result<()> {
+        self.drive_bots();
+        self.game_state.update(delta_time)?;
+        self.handle_eliminated()?;
+        if let Some(hook) = self.on_tick.as_mut() {
+            hook(&mut self.game_state, self.current_tick);
+        }
+        self.position_history
+            .record(self.current_tick, self.game_state.players());
+
+        for (player1, player2) in self.game_state.last_collisions() {
+            self.event_log.record(
+                self.current_tick,
+                EventKind::Collision {
+                    player1: player1.clone(),
+                    player2: player2.clone(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // Eliminated players keep their connection and may Join again
+    fn handle_eliminated(&mut self) -> Result<()> {
+        for player_id in self.game_state.last_eliminated().to_vec() {
+            println!("Player {} eliminated", player_id);
+            self.bots.remove(&player_id);
+            self.position_history.remove(&player_id);
+            self.event_log.record(
+                self.current_tick,
+                EventKind::Eliminated {
+                    player_id: player_id.clone(),
+                },
+            );
+            self.broadcast(&ServerMessage::PlayerLeft {
+                player_id: player_id.clone(),
+            })?;
+            if let Some(slot) = self.slots.release(&player_id) {
+                self.broadcast(&ServerMessage::SlotReleased { slot })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn drive_bots(&mut self) {
+        for (bot_id, bot) in self.bots.iter_mut() {
+            let Some(velocity) = bot.steer(bot_id, &mut self.game_state) else {
+                continue;
+            };
+            if let Some(player) = self.game_state.players.get_mut(bot_id) {
+                player.velocity = velocity;
+            }
+            self.game_state.note_input(bot_id);
+        }
+    }
+
+    fn broadcast_state(&mut self, tick: u64) -> Result<()> {
+        // This tick's state will replace one the broadcast thread never sent, so
+        // clients need a full snapshot rather than a delta or NoChange against it
+        let resync = self
+            .broadcaster
+            .as_ref()
+            .is_some_and(Broadcaster::has_pending_state);
+        let players = self.game_state.players();
+        if let Some(keepalive_ticks) = self.idle_keepalive_ticks.filter(|_| !resync) {
+            let unchanged = self
+                .last_broadcast_players
+                .as_ref()
+                .is_some_and(|last| same_visible_state(last, players));
+
+            if unchanged {
+                if tick.saturating_sub(self.last_broadcast_tick) >= keepalive_ticks.max(1) {
+                    self.last_broadcast_tick = tick;
+                    return self.queue_state(ServerMessage::NoChange { tick }, Audience::Players);
+                }
+                return Ok(());
+            }
+        }
+
+        let server_time = Timestamp::now();
+        let base_tick = self.last_state_tick;
+        let base = if self.idle_keepalive_ticks.is_some() || self.delta_keyframe_ticks.is_some() {
+            self.last_broadcast_players.replace(players.clone())
+        } else {
+            None
+        };
+        self.last_broadcast_tick = tick;
+        self.last_state_tick = tick;
+
+        if let (Some(keyframe_ticks), Some(base)) = (self.delta_keyframe_ticks, base) {
+            if !resync && tick.saturating_sub(self.last_keyframe_tick) < keyframe_ticks.max(1) {
+                let delta = GameStateDelta::between(
+                    base_tick,
+                    &base,
+                    tick,
+                    self.game_state.players(),
+                    server_time,
+                );
+                return self.queue_state(ServerMessage::GameStateDelta(delta), Audience::Players);
+            }
+        }
+        self.last_keyframe_tick = tick;
+
+        let chunk_size = self.max_players_per_update.unwrap_or(usize::MAX).max(1);
+        let bounds = Some(self.game_state.rules().bounds());
+
+        // Split oversized snapshots into parts the client reassembles by tick
+        if self.slot_encoding {
+            let mut players: Vec<(u32, PlayerState)> = self
+                .game_state
+                .players()
+                .iter()
+                .filter_map(|(id, state)| Some((self.slots.get(id)?, state.clone())))
+                .collect();
+            players.sort_by_key(|(slot, _)| *slot);
+            let chunks = snapshot_parts(&players, chunk_size);
+            let of = chunks.len() as u32;
+
+            for (part, chunk) in chunks.into_iter().enumerate() {
+                let snapshot = SlotSnapshot {
+                    tick,
+                    players: chunk.to_vec(),
+                    server_time: server_time.clone(),
+                    part: part as u32,
+                    of,
+                    bounds: if part == 0 { bounds } else { None },
+                };
+                self.queue_state(ServerMessage::SlotSnapshot(snapshot), Audience::Players)?;
+            }
+            return Ok(());
+        }
+
+        let players: Vec<(String, PlayerState)> = self
+            .game_state
+            .players
+            .iter()
+            .map(|(id, state)| (id.clone(), state.clone()))
+            .collect();
+        let chunks = snapshot_parts(&players, chunk_size);
+        let of = chunks.len() as u32;
+
+        for (part, chunk) in chunks.into_iter().enumerate() {
+            let update = GameStateUpdate {
+                part: part as u32,
+                of,
+                bounds: if part == 0 { bounds } else { None },
+                ..GameStateUpdate::new(tick, chunk.iter().cloned().collect(), server_time.clone())
+            };
+            self.queue_state(ServerMessage::GameState(update), Audience::Players)?;
+        }
+        Ok(())
+    }
+
+    // Spectators always get full snapshots, on their own slower cadence
+    fn broadcast_spectator_state(&mut self, delta_time: Duration) -> Result<()> {
+        if self.spectators.is_empty() {
+            self.spectator_accumulator = Duration::ZERO;
+            return Ok(());
+        }
+
+        self.spectator_accumulator += delta_time;
+        if self.spectator_accumulator < self.spectator_rate {
+            return Ok(());
+        }
+        // Drop any backlog rather than sending a burst after a long frame
+        self.spectator_accumulator = self
+            .spectator_accumulator
+            .saturating_sub(self.spectator_rate)
+            .min(self.spectator_rate);
+
+        let update = GameStateUpdate {
+            bounds: Some(self.game_state.rules().bounds()),
+            ..GameStateUpdate::new(
+                self.current_tick,
+                self.game_state.players().clone(),
+                Timestamp::now(),
+            )
+        };
+        self.queue_state(ServerMessage::GameState(update), Audience::Spectators)
+    }
+
+    // Per-tick state (snapshots, deltas, keepalives). Queued for the broadcast thread
+    // when there is one, otherwise sent right away
+    fn queue_state(&mut self, message: ServerMessage, audience: Audience) -> Result<()> {
+        if self.broadcaster.is_some() {
+            self.pending_state.push((message, audience));
+            return Ok(());
+        }
+        let encoded = encode_state(&message, &self.state_encoding());
+        self.send_encoded(encoded, audience)
+    }
+
+    // Hands this tick's queued state to the broadcast thread, replacing any earlier
+    // tick it hasn't started on
+    fn flush_state(&mut self) {
+        let Some(broadcaster) = &self.broadcaster else {
+            return;
+        };
+        for size in broadcaster.take_sizes() {
+            self.stats.message_sizes.record(size);
+        }
+        if self.pending_state.is_empty() {
+            return;
+        }
+
+        let messages = std::mem::take(&mut self.pending_state)
+            .into_iter()
+            .map(|(message, audience)| (message, self.recipients(audience)))
+            .collect();
+        broadcaster.submit(BroadcastJob::State {
+            messages,
+            encoding: self.state_encoding(),
+        });
+    }
+
+    fn recipients(&self, audience: Audience) -> Vec<Sender<Vec<u8>>> {
+        self.players
+            .iter()
+            .filter(|(player_id, _)| self.in_audience(player_id, audience))
+            .map(|(_, player)| player.sender.clone())
+            .collect()
+    }
+
+    fn state_encoding(&self) -> StateEncoding {
+        StateEncoding {
+            float_precision: self.float_precision,
+            snapshot_fields: self.snapshot_fields.clone(),
+            sorted_players: self.sorted_players,
+        }
+    }
+
+    fn in_audience(&self, player_id: &str, audience: Audience) -> bool {
+        let spectator = self.spectators.contains(player_id);
+        match audience {
+            Audience::Everyone => true,
+            Audience::Players => !spectator,
+            Audience::Spectators => spectator,
+        }
+    }
+
+    // Sends to every connection, cleaning up any whose channel has closed
+    fn broadcast<T: Serialize>(&mut self, message: &T) -> Result<()> {
+        self.broadcast_to(message, Audience::Everyone)
+    }
+
+    fn broadcast_to<T: Serialize>(&mut self, message: &T, audience: Audience) -> Result<()> {
+        let encoded = encode(message, self.float_precision);
+        self.send_encoded(encoded, audience)
+    }
+
+    fn send_encoded(&mut self, encoded: Result<Vec<u8>>, audience: Audience) -> Result<()> {
+        let message = match encoded {
+            Ok(message) => message,
+            Err(e) => {
+                // A bad payload skips this broadcast rather than stopping the game loop
+                eprintln!("Failed to serialize broadcast: {}", e);
+                return Ok(());
+            }
+        };
+        self.stats.message_sizes.record(message.len());
+        if let Some(broadcaster) = &self.broadcaster {
+            broadcaster.submit(BroadcastJob::Message {
+                recipients: self.recipients(audience),
+                bytes: message,
+            });
+            return Ok(());
+        }
+
+        let mut disconnected_players = Vec::new();
+
+        for (player_id, player) in &self.players {
+            if !self.in_audience(player_id, audience) {
+                continue;
+            }
+            if player.sender.send(message.clone()).is_err() {
+                disconnected_players.push(player_id.clone());
+            }
+        }
+
+        // A closed channel is a lost connection, so the reconnect grace still applies
+        for player_id in disconnected_players {
+            self.handle_disconnect(&player_id)?;
+        }
+
+        Ok(())
+    }
+
+    // Keeps a player with an identity token in the world during the reconnect grace
+    // period instead of removing them; only their connection goes away
+    fn handle_disconnect(&mut self, player_id: &str) -> Result<()> {
+        if self.retained.contains_key(player_id) {
+            return Ok(());
+        }
+        let grace = self.game_state.rules.reconnect_grace;
+        let has_identity = self.identities.values().any(|holder| holder == player_id);
+        let Some(player) = self.game_state.players.get_mut(player_id) else {
+            return self.remove_player(player_id);
+        };
+        if grace.is_zero() || !has_identity {
+            return self.remove_player(player_id);
+        }
+
+        player.velocity = Vector2::default();
+        self.players.remove(player_id);
+        self.clock_skew.remove(player_id);
+        self.retained
+            .insert(player_id.to_string(), Timestamp::now());
+        println!(
+            "Player {} disconnected, retained for {:?}",
+            player_id, grace
+        );
+        Ok(())
+    }
+
+    // Removes retained players whose grace period has run out by `now`
+    fn reap_disconnected(&mut self, now: &Timestamp) -> Result<()> {
+        let grace = self.game_state.rules.reconnect_grace;
+        let expired: Vec<String> = self
+            .retained
+            .iter()
+            .filter(|(_, left_at)| now.duration_since(left_at) >= grace)
+            .map(|(player_id, _)| player_id.clone())
+            .collect();
+        for player_id in expired {
+            self.remove_player(&player_id)?;
+        }
+        Ok(())
+    }
+
+    fn remove_player(&mut self, player_id: &str) -> Result<()> {
+        self.players.remove(player_id);
+        self.retained.remove(player_id);
+        self.clock_skew.remove(player_id);
+        self.identities.retain(|_, holder| holder != player_id);
+        self.bots.remove(player_id);
+        self.position_history.remove(player_id);
+        self.spectators.remove(player_id);
+        let joined = self.game_state.get_player_state(player_id).is_some();
+        if joined {
+            self.event_log.record(
+                self.current_tick,
+                EventKind::PlayerLeft {
+                    player_id: player_id.to_string(),
+                },
+            );
+        }
+        self.game_state.remove_player(player_id);
+        println!("Player {} disconnected", player_id);
+
+        // Sent right away rather than waiting for the next snapshot or delta
+        if joined {
+            self.broadcast(&ServerMessage::PlayerLeft {
+                player_id: player_id.to_string(),
+            })?;
+        }
+
+        if let Some(slot) = self.slots.release(player_id) {
+            self.broadcast(&ServerMessage::SlotReleased { slot })?;
+        }
+        Ok(())
+    }
+}
+
+// ----------- TESTS ---------
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::math::{approx_eq_f32, EPSILON};
     use std::time::Duration;
 
+    fn next_message(receiver: &Receiver<Vec<u8>>) -> ServerMessage {
+        let bytes = receiver.try_recv().expect("No message sent");
+        serde_json::from_slice(&bytes).expect("Invalid message")
+    }
+
+    #[test]
+    fn test_game_state_update() {
+        let mut state = GameState::new();
+        let player_id = "test_player".to_string();
+        let initial_state = PlayerState {
+            position: Vector2 { x: 0.0, y: 0.0 },
+            velocity: Vector2 { x: 1.0, y: 0.0 },
+            last_update: Timestamp::now(),
+            ..Default::default()
+        };
+
+        // Add player
+        state.add_player(player_id.clone(), initial_state);
+        assert_eq!(state.get_player_count(), 1);
+
+        // Update game state
+        let delta = Duration::from_millis(100); // 100ms
+        state.update(delta).expect("Update failed");
+
+        // Check position updated according to velocity
+        if let Some(updated_state) = state.get_player_state(&player_id) {
+            assert!(
+                updated_state.position.x > 0.0,
+                "Player should have moved right"
+            );
+            assert_eq!(
+                updated_state.position.y, 0.0,
+                "Player should not have moved vertically"
+            );
+        } else {
+            panic!("Player state not found");
+        }
+    }
+
+    #[test]
+    fn test_collision_detection() {
+        let mut state = GameState::new();
+        let rules = GameRules::default();
+
+        // Add two players close to each other
+        let player1 = PlayerState {
+            position: Vector2 { x: 0.0, y: 0.0 },
+            velocity: Vector2 { x: 1.0, y: 0.0 },
+            last_update: Timestamp::now(),
+            ..Default::default()
+        };
+        let player2 = PlayerState {
+            position: Vector2 {
+                x: rules.collision_radius - 1.0,
+                y: 0.0,
+            },
+            velocity: Vector2 { x: -1.0, y: 0.0 },
+            last_update: Timestamp::now(),
+            ..Default::default()
+        };
+
+        state.add_player("player1".to_string(), player1);
+        state.add_player("player2".to_string(), player2);
+
+        // Update should trigger collision resolution
+        state
+            .update(Duration::from_millis(16))
+            .expect("Update failed");
+
+        // Check that players were pushed apart
+        let p1_state = state
+            .get_player_state("player1")
+            .expect("Player1 not found");
+        let p2_state = state
+            .get_player_state("player2")
+            .expect("Player2 not found");
+
+        let distance = ((p1_state.position.x - p2_state.position.x).powi(2)
+            + (p1_state.position.y - p2_state.position.y).powi(2))
+        .sqrt();
+
+        assert!(
+            distance >= rules.collision_radius,
+            "Players should be pushed apart to at least collision radius"
+        );
+    }
+
+    #[test]
+    fn test_scaled_rules_keep_proportions() {
+        let rules = GameRules::default();
+        let scaled = rules.scaled(2.0);
+
+        assert_eq!(scaled.map_bounds, (-200.0, 200.0));
+        assert_eq!(scaled.collision_radius, 20.0);
+        assert_eq!(scaled.max_velocity, 20.0);
+        assert_eq!(scaled.separation_strength, rules.separation_strength);
+        assert!(scaled.validate().is_ok());
+
+        // A negative factor flips the bounds, which validate catches
+        assert!(rules.scaled(-1.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_impulse_scaled_by_mass_and_clamped() {
+        let mut state = GameState::new();
+        state.add_player(
+            "heavy".to_string(),
+            PlayerState {
+                mass: 2.0,
+                ..Default::default()
+            },
+        );
+
+        state
+            .apply_impulse("heavy", Vector2 { x: 6.0, y: -4.0 })
+            .unwrap();
+        let velocity = state.get_player_state("heavy").unwrap().velocity;
+        assert_eq!((velocity.x, velocity.y), (3.0, -2.0));
+
+        // Default max_velocity is 10
+        state
+            .apply_impulse("heavy", Vector2 { x: 100.0, y: 0.0 })
+            .unwrap();
+        assert_eq!(state.get_player_state("heavy").unwrap().velocity.x, 10.0);
+
+        assert!(state
+            .apply_impulse("missing", Vector2 { x: 1.0, y: 0.0 })
+            .is_err());
+
+        // Non-finite impulses are refused and leave the velocity alone
+        for impulse in [f32::NAN, f32::INFINITY] {
+            assert!(state
+                .apply_impulse("heavy", Vector2 { x: impulse, y: 0.0 })
+                .is_err());
+        }
+        assert_eq!(state.get_player_state("heavy").unwrap().velocity.x, 10.0);
+        state.update(Duration::from_millis(16)).unwrap();
+    }
+
+    #[test]
+    fn test_equal_mass_collision_conserves_kinetic_energy() {
+        let mut state = GameState::new();
+        let rules = GameRules::default();
+        for (id, x, velocity) in [
+            ("a", 0.0, Vector2 { x: 4.0, y: 1.0 }),
+            (
+                "b",
+                rules.collision_radius - 1.0,
+                Vector2 { x: -2.0, y: 0.0 },
+            ),
+        ] {
+            state.add_player(
+                id.to_string(),
+                PlayerState {
+                    position: Vector2 { x, y: 0.0 },
+                    velocity,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // 0.5 * (16 + 1) + 0.5 * 4
+        let before = state.total_kinetic_energy();
+        assert!(approx_eq_f32(before, 10.5, EPSILON));
+
+        state.update(Duration::from_millis(16)).unwrap();
+        assert_eq!(state.last_collisions().len(), 1);
+        let after = state.total_kinetic_energy();
+        assert!(
+            approx_eq_f32(after, before, EPSILON),
+            "Energy changed from {} to {}",
+            before,
+            after
+        );
+    }
+
+    #[test]
+    fn test_per_player_max_speed_overrides_rules() {
+        let mut state = GameState::new();
+        let rules = GameRules::default();
+        for (id, y, max_speed) in [("boosted", 50.0, Some(30.0)), ("normal", -50.0, None)] {
+            state.add_player(
+                id.to_string(),
+                PlayerState {
+                    position: Vector2 { x: -90.0, y },
+                    velocity: Vector2 { x: 25.0, y: 0.0 },
+                    max_speed,
+                    ..Default::default()
+                },
+            );
+        }
+
+        for _ in 0..10 {
+            state.update(Duration::from_millis(100)).unwrap();
+        }
+
+        let boosted = state.get_player_state("boosted").unwrap();
+        let normal = state.get_player_state("normal").unwrap();
+        assert_eq!(boosted.velocity.x, 25.0);
+        assert_eq!(normal.velocity.x, rules.max_velocity);
+        assert!(boosted.position.x > normal.position.x + 10.0);
+    }
+
+    #[test]
+    fn test_long_frame_is_clamped_to_prevent_tunneling() {
+        let mut state = GameState::new();
+        for (id, x, vx) in [("left", -5.5, 10.0), ("right", 5.5, -10.0)] {
+            state.add_player(
+                id.to_string(),
+                PlayerState {
+                    position: Vector2 { x, y: 0.0 },
+                    velocity: Vector2 { x: vx, y: 0.0 },
+                    ..Default::default()
+                },
+            );
+        }
+
+        state.update(Duration::from_secs(2)).expect("Update failed");
+
+        let left = state.get_player_state("left").unwrap().position.x;
+        let right = state.get_player_state("right").unwrap().position.x;
+        assert!(left < right, "Players passed through each other");
+        assert_eq!(state.last_collisions().len(), 1);
+    }
+
+    #[test]
+    fn test_spawn_grace_excludes_new_player_from_collisions() {
+        let mut state = GameState::with_rules(GameRules {
+            spawn_grace: Duration::from_millis(50),
+            ..Default::default()
+        });
+        state.add_player("existing".to_string(), PlayerState::default());
+        state.spawn_player(
+            "spawned".to_string(),
+            PlayerState {
+                position: Vector2 { x: 2.0, y: 0.0 },
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            state.get_player_state("spawned").unwrap().status,
+            PlayerStatus::SpawnProtected
+        );
+
+        state
+            .update(Duration::from_millis(20))
+            .expect("Update failed");
+        let spawned = state.get_player_state("spawned").unwrap();
+        assert_eq!(spawned.position.x, 2.0, "Protected player was displaced");
+        assert!(state.last_collisions().is_empty());
+
+        state
+            .update(Duration::from_millis(40))
+            .expect("Update failed");
+        let spawned = state.get_player_state("spawned").unwrap();
+        assert_eq!(spawned.status, PlayerStatus::Active);
+        assert!(
+            spawned.position.x > 2.0,
+            "Collision should resolve after grace"
+        );
+        assert_eq!(state.last_collisions().len(), 1);
+    }
+
+    #[test]
+    fn test_collisions_disabled_leaves_overlap() {
+        let mut state = GameState::with_rules(GameRules {
+            enable_collisions: false,
+            ..Default::default()
+        });
+        let velocity = Vector2 { x: 1.0, y: -1.0 };
+        for (id, x) in [("player1", 0.0), ("player2", 2.0)] {
+            state.add_player(
+                id.to_string(),
+                PlayerState {
+                    position: Vector2 { x, y: 0.0 },
+                    velocity,
+                    ..Default::default()
+                },
+            );
+        }
+
+        state.update(Duration::ZERO).expect("Update failed");
+
+        for (id, x) in [("player1", 0.0), ("player2", 2.0)] {
+            let player = state.get_player_state(id).unwrap();
+            assert_eq!(player.position.x, x);
+            assert_eq!(player.position.y, 0.0);
+            assert_eq!(player.velocity.x, velocity.x);
+            assert_eq!(player.velocity.y, velocity.y);
+        }
+        assert!(state.last_collisions().is_empty());
+    }
+
+    #[test]
+    fn test_heavier_player_is_not_displaced() {
+        let rules = GameRules::default();
+        let gap = rules.collision_radius - 4.0;
+
+        for heavy_mass in [1_000_000.0, f32::INFINITY] {
+            let mut state = GameState::new();
+            state.add_player(
+                "heavy".to_string(),
+                PlayerState {
+                    mass: heavy_mass,
+                    ..Default::default()
+                },
+            );
+            state.add_player(
+                "light".to_string(),
+                PlayerState {
+                    position: Vector2 { x: gap, y: 0.0 },
+                    ..Default::default()
+                },
+            );
+
+            state.update(Duration::ZERO).expect("Update failed");
+
+            let heavy = state.get_player_state("heavy").unwrap();
+            let light = state.get_player_state("light").unwrap();
+            assert!(
+                heavy.position.x.abs() < 1e-3,
+                "Heavy player should stay put"
+            );
+            assert!(
+                (light.position.x - rules.collision_radius).abs() < 1e-3,
+                "Light player should be displaced by the full overlap"
+            );
+        }
+    }
+
+    #[test]
+    fn test_bounds_checking() {
+        let mut state = GameState::new();
+        let rules = GameRules::default();
+
+        // Add player at edge of map with velocity pointing outward
+        let player_state = PlayerState {
+            position: Vector2 {
+                x: rules.map_bounds.1,
+                y: 0.0,
+            },
+            velocity: Vector2 { x: 10.0, y: 0.0 },
+            last_update: Timestamp::now(),
+            ..Default::default()
+        };
+
+        state.add_player("player1".to_string(), player_state);
+        state
+            .update(Duration::from_millis(16))
+            .expect("Update failed");
+
+        // Check that player position is clamped to map bounds
+        let updated_state = state.get_player_state("player1").expect("Player not found");
+        assert!(
+            updated_state.position.x <= rules.map_bounds.1,
+            "Player should not move beyond map bounds"
+        );
+    }
+
+    #[test]
+    fn test_wall_clamp_zeroes_velocity() {
+        let mut state = GameState::new();
+        let rules = GameRules::default();
+        let player_state = PlayerState {
+            position: Vector2 {
+                x: rules.map_bounds.1,
+                y: 0.0,
+            },
+            velocity: Vector2 { x: 10.0, y: 1.0 },
+            last_update: Timestamp::now(),
+            ..Default::default()
+        };
+
+        state.add_player("player1".to_string(), player_state);
+        state
+            .update(Duration::from_millis(16))
+            .expect("Update failed");
+
+        let updated_state = state.get_player_state("player1").expect("Player not found");
+        assert_eq!(updated_state.position.x, rules.map_bounds.1);
+        assert_eq!(
+            updated_state.velocity.x, 0.0,
+            "Velocity into the wall should be zeroed"
+        );
+        assert_eq!(
+            updated_state.velocity.y, 1.0,
+            "Velocity along the wall is kept"
+        );
+    }
+
+    #[test]
+    fn test_wall_bounce_reflects_velocity() {
+        let rules = GameRules {
+            boundary_mode: BoundaryMode::Bounce { restitution: 0.5 },
+            ..Default::default()
+        };
+        let mut state = GameState::with_rules(rules.clone());
+        let player_state = PlayerState {
+            position: Vector2 {
+                x: rules.map_bounds.1,
+                y: 0.0,
+            },
+            velocity: Vector2 { x: 10.0, y: 0.0 },
+            last_update: Timestamp::now(),
+            ..Default::default()
+        };
+
+        state.add_player("player1".to_string(), player_state);
+        state
+            .update(Duration::from_millis(16))
+            .expect("Update failed");
+
+        let updated_state = state.get_player_state("player1").expect("Player not found");
+        assert_eq!(updated_state.velocity.x, -5.0);
+    }
+
+    #[test]
+    fn test_eliminate_removes_players_outside_bounds() {
+        let rules = GameRules {
+            boundary_mode: BoundaryMode::Eliminate,
+            ..Default::default()
+        };
+        let edge = rules.map_bounds.1;
+        let mut state = GameState::with_rules(rules);
+        for (id, x, velocity) in [("leaving", edge, 10.0), ("staying", edge - 1.0, 0.0)] {
+            let player = PlayerState {
+                position: Vector2 { x, y: 0.0 },
+                velocity: Vector2 {
+                    x: velocity,
+                    y: 0.0,
+                },
+                ..Default::default()
+            };
+            state.add_player(id.to_string(), player);
+        }
+
+        state.update(Duration::from_millis(16)).unwrap();
+
+        assert!(state.get_player_state("leaving").is_none());
+        assert!(state.get_player_state("staying").is_some());
+        assert_eq!(state.last_eliminated(), &["leaving".to_string()]);
+
+        state.update(Duration::from_millis(16)).unwrap();
+        assert!(state.last_eliminated().is_empty());
+    }
+
+    #[test]
+    fn test_invariants_catch_nan_position() {
+        let mut state = GameState::new();
+        state.add_player("player1".to_string(), PlayerState::default());
+        assert_eq!(state.check_invariants(), Ok(()));
+
+        state.players.get_mut("player1").unwrap().position.x = f32::NAN;
+        let error = state.check_invariants().unwrap_err();
+        assert!(error.contains("non-finite position"), "{}", error);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "non-finite position")]
+    fn test_update_asserts_invariants_in_debug() {
+        let mut state = GameState::new();
+        let player = PlayerState {
+            position: Vector2 {
+                x: f32::NAN,
+                y: 0.0,
+            },
+            ..Default::default()
+        };
+        state.add_player("player1".to_string(), player);
+        let _ = state.update(Duration::from_millis(16));
+    }
+
+    #[test]
+    fn test_player_ids() {
+        let mut state = GameState::new();
+        state.add_player("player1".to_string(), PlayerState::default());
+        state.add_player("player2".to_string(), PlayerState::default());
+        state.add_player("player3".to_string(), PlayerState::default());
+        state.remove_player("player2");
+
+        let mut ids: Vec<&String> = state.player_ids().collect();
+        ids.sort();
+
+        assert_eq!(ids, vec!["player1", "player3"]);
+    }
+
+    #[test]
+    fn test_nearest_player_excludes_self() {
+        let mut game_state = GameState::new();
+        for (id, x) in [("me", 0.0), ("near", 3.0), ("far", -50.0)] {
+            game_state.add_player(
+                id.to_string(),
+                PlayerState {
+                    position: Vector2 { x, y: 4.0 },
+                    ..Default::default()
+                },
+            );
+        }
+
+        let origin = Vector2 { x: 0.0, y: 0.0 };
+        let (id, distance) = game_state.nearest_player(origin, Some("me")).unwrap();
+        assert_eq!(id, "near");
+        assert!((distance - 5.0).abs() < 1e-6);
+
+        let (id, _) = game_state.nearest_player(origin, None).unwrap();
+        assert_eq!(id, "me");
+        assert!(GameState::new().nearest_player(origin, None).is_none());
+    }
+
+    #[test]
+    fn test_equidistant_ties_broken_by_id() {
+        // Each GameState gets a freshly seeded HashMap, so repeat to catch order dependence
+        for _ in 0..20 {
+            let mut game_state = GameState::new();
+            for (id, x) in [("zed", -5.0), ("amy", 5.0), ("mid", 0.0)] {
+                game_state.add_player(
+                    id.to_string(),
+                    PlayerState {
+                        position: Vector2 { x, y: 0.0 },
+                        ..Default::default()
+                    },
+                );
+            }
+
+            let origin = Vector2 { x: 0.0, y: 0.0 };
+            let (id, _) = game_state.nearest_player(origin, Some("mid")).unwrap();
+            assert_eq!(id, "amy");
+
+            let ids: Vec<String> = game_state
+                .players_within(origin, 5.0)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(ids, vec!["amy", "mid", "zed"]);
+
+            game_state.update(Duration::from_millis(16)).unwrap();
+            let pairs: Vec<(&str, &str)> = game_state
+                .last_collisions()
+                .iter()
+                .map(|(a, b)| (a.as_str(), b.as_str()))
+                .collect();
+            assert_eq!(pairs, vec![("amy", "mid"), ("mid", "zed")]);
+        }
+    }
+
+    #[test]
+    fn test_players_within_radius() {
+        let mut game_state = GameState::new();
+        for (id, x, y) in [
+            ("center", 0.0, 0.0),
+            ("inside", 3.0, 4.0),
+            ("boundary", 0.0, 10.0),
+            ("outside", 8.0, 8.0),
+        ] {
+            game_state.add_player(
+                id.to_string(),
+                PlayerState {
+                    position: Vector2 { x, y },
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut ids: Vec<String> = game_state
+            .players_within(Vector2 { x: 0.0, y: 0.0 }, 10.0)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+
+        assert_eq!(ids, vec!["boundary", "center", "inside"]);
+        assert!(game_state
+            .players_within(Vector2 { x: 50.0, y: 50.0 }, 5.0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_seeded_spawns_are_reproducible() {
+        let rules = GameRules {
+            spawn_strategy: SpawnStrategy::Random,
+            rng_seed: Some(42),
+            ..Default::default()
+        };
+        let mut server_a = GameServer::with_rules("127.0.0.1:0", rules.clone()).unwrap();
+        let mut server_b = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+
+        for id in ["player1", "player2", "player3"] {
+            server_a
+                .handle_player_join(id, None, None)
+                .expect("Join failed");
+            server_b
+                .handle_player_join(id, None, None)
+                .expect("Join failed");
+
+            let spawn_a = server_a.game_state.get_player_state(id).unwrap().position;
+            let spawn_b = server_b.game_state.get_player_state(id).unwrap().position;
+            assert_eq!(spawn_a.x, spawn_b.x);
+            assert_eq!(spawn_a.y, spawn_b.y);
+        }
+    }
+
+    #[test]
+    fn test_ping_answered_with_pong() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+
+        let sent_at = Timestamp::now();
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::Ping {
+                    sent_at: sent_at.clone(),
+                },
+            )
+            .expect("Ping failed");
+
+        match next_message(&receiver) {
+            ServerMessage::Pong {
+                sent_at: echoed, ..
+            } => {
+                assert_eq!(echoed.duration_since(&sent_at), Duration::ZERO);
+                assert_eq!(sent_at.duration_since(&echoed), Duration::ZERO);
+            }
+            other => panic!("Expected Pong, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reliable_message_acked_then_handled() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::Reliable {
+                    seq: 5,
+                    message: Box::new(ClientMessage::Ping {
+                        sent_at: Timestamp::now(),
+                    }),
+                },
+            )
+            .expect("Reliable message failed");
+
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::Ack { seq: 5 }
+        ));
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::Pong { .. }
+        ));
+    }
+
+    #[test]
+    fn test_world_info_advertises_configured_rates() {
+        let rules = GameRules::default();
+        assert_eq!(rules.world_info().tick_rate_ms, 16);
+        assert_eq!(rules.world_info().max_velocity, rules.max_velocity);
+
+        let mut server = GameServerBuilder::new("127.0.0.1:0")
+            .tick_rate(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert_eq!(server.tick_rate(), Duration::from_millis(50));
+        assert_eq!(server.broadcast_rate(), Duration::from_millis(50));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+        next_message(&receiver); // JoinAccepted
+
+        match next_message(&receiver) {
+            ServerMessage::WorldInfo(info) => {
+                assert_eq!(info.tick_rate_ms, 50);
+                assert_eq!(info.broadcast_rate_ms, 50);
+            }
+            other => panic!("Expected WorldInfo, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_init_data_sent_after_join() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_init_data(Some(b"level-1".to_vec()));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+
+        server.handle_player_join("player1", None, None).unwrap();
+
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::JoinAccepted { .. }
+        ));
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::WorldInfo(_)
+        ));
+        match next_message(&receiver) {
+            ServerMessage::InitData { payload } => assert_eq!(payload, b"level-1"),
+            other => panic!("Expected InitData, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_server_full_rejection_code() {
+        let rules = GameRules {
+            max_players: Some(1),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender1, _receiver1) = std::sync::mpsc::channel();
+        let (sender2, receiver2) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender1);
+        server.add_connection("player2".to_string(), sender2);
+
+        server
+            .handle_player_join("player1", None, None)
+            .expect("Join failed");
+        server
+            .handle_player_join("player2", None, None)
+            .expect("Join failed");
+
+        assert_eq!(server.game_state.get_player_count(), 1);
+        match next_message(&receiver2) {
+            ServerMessage::Error { error_code, .. } => {
+                assert_eq!(error_code, ErrorCode::ServerFull)
+            }
+            other => panic!("Expected Error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_run_for_returns_after_ticks() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let tick_rate = server.tick_rate;
+
+        let start = std::time::Instant::now();
+        server.run_for(10).expect("Run failed");
+        let elapsed = start.elapsed();
+
+        assert_eq!(server.current_tick, 10);
+        assert!(
+            elapsed >= tick_rate * 9,
+            "Returned too early: {:?}",
+            elapsed
+        );
+        assert!(
+            elapsed < tick_rate * 10 + Duration::from_millis(500),
+            "Returned too late: {:?}",
+            elapsed
+        );
+    }
+
+    // Collects formatted log output so tests can assert on warnings
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slow_tick_hook_logs_slow_phase() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_on_tick(|_, _| std::thread::sleep(Duration::from_millis(20)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            server.run_tick(Duration::from_millis(16)).unwrap();
+        });
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            output.contains("WARN"),
+            "Expected a warning, got: {}",
+            output
+        );
+        assert!(output.contains("Slow update phase"), "Got: {}", output);
+        assert!(!output.contains("Slow broadcast phase"), "Got: {}", output);
+    }
+
+    #[test]
+    fn test_with_listener_serves_resolved_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut server = GameServer::with_listener(listener, GameRules::default()).unwrap();
+        let address = server.local_addr().to_string();
+        assert!(!address.ends_with(":0"), "Port should be resolved");
+
+        let server_thread = std::thread::spawn(move || server.run_for(60));
+
+        let mut client = crate::client::GameClient::new(&address).expect("Failed to connect");
+        client.connect().expect("Failed to join");
+        assert!(client.own_id().is_some());
+
+        server_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_clients_on_each_listener_share_one_game() {
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut server = GameServerBuilder::new("")
+            .listener(first)
+            .listener(second)
+            .build()
+            .unwrap();
+        let addresses = server.local_addrs().to_vec();
+        assert_eq!(addresses.len(), 2);
+        assert_ne!(addresses[0], addresses[1]);
+        let server_thread = std::thread::spawn(move || server.run_for(120));
+
+        let mut clients: Vec<_> = addresses
+            .iter()
+            .map(|address| {
+                let mut client =
+                    crate::client::GameClient::new(address).expect("Failed to connect");
+                client.connect().expect("Failed to join");
+                client
+            })
+            .collect();
+        let start = std::time::Instant::now();
+        while clients[0].all_players().count() < 2 && start.elapsed() < Duration::from_secs(2) {
+            clients[0].update().unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let other = clients[1].own_id().unwrap().to_string();
+        assert!(clients[0].get_player_state(&other).is_some());
+        server_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_taken_address_fails_build() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = taken.local_addr().unwrap().to_string();
+
+        assert!(GameServer::new(&address).is_err());
+        let result = GameServerBuilder::new("127.0.0.1:0").bind(&address).build();
+        match result {
+            Err(GameError::NetworkError(message)) => assert!(message.contains(&address)),
+            _ => panic!("Expected a bind error"),
+        }
+
+        // Port 0 resolves at build time
+        let server = GameServer::new("127.0.0.1:0").unwrap();
+        assert!(!server.local_addr().ends_with(":0"));
+    }
+
+    #[test]
+    fn test_joining_client_receives_world_bounds() {
+        let rules = GameRules {
+            map_bounds: (-40.0, 40.0),
+            ..Default::default()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut server = GameServer::with_listener(listener, rules).unwrap();
+        let address = server.local_addr().to_string();
+        let server_thread = std::thread::spawn(move || server.run_for(60));
+
+        let mut client = crate::client::GameClient::new(&address).expect("Failed to connect");
+        client.connect().expect("Failed to join");
+        let start = std::time::Instant::now();
+        while client.world_bounds().is_none() && start.elapsed() < Duration::from_secs(2) {
+            client.update().unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(client.world_bounds(), Some((-40.0, 40.0)));
+        server_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_spectator_outlives_read_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut server = GameServerBuilder::new("")
+            .listener(listener)
+            .read_deadline(Some(Duration::from_millis(300)))
+            .build()
+            .unwrap();
+        let address = server.local_addr().to_string();
+        let server_thread = std::thread::spawn(move || {
+            server.run_for(120).unwrap();
+            server
+        });
+
+        let mut client = crate::client::GameClient::new(&address).expect("Failed to connect");
+        client.ping_loop(Duration::from_millis(100));
+        client.spectate().expect("Failed to spectate");
+
+        // Two seconds of ticks, several deadlines' worth
+        let server = server_thread.join().unwrap();
+        assert_eq!(server.spectators.len(), 1);
+    }
+
+    #[test]
+    fn test_panicking_reader_thread_still_removes_player() {
+        use std::io::Write;
+
+        let mut builder = GameServerBuilder::new("127.0.0.1:0");
+        // The reader thread dies on the first Ping it reads
+        builder.network_config.parse_hook = Some(|line| {
+            if line.contains("Ping") {
+                panic!("Simulated reader panic");
+            }
+            serde_json::from_str(line)
+        });
+        let mut server = builder.build().unwrap();
+        server.start_network();
+
+        let mut client = std::net::TcpStream::connect(server.local_addr()).unwrap();
+        let mut send = |message: &ClientMessage| {
+            let json = serde_json::to_string(message).unwrap();
+            client.write_all(format!("{}\n", json).as_bytes()).unwrap();
+        };
+        let wait_for_players = |server: &mut GameServer, count: usize| {
+            let deadline = std::time::Instant::now() + Duration::from_secs(2);
+            while server.game_state.get_player_count() != count {
+                assert!(
+                    std::time::Instant::now() < deadline,
+                    "Expected {} players",
+                    count
+                );
+                std::thread::sleep(Duration::from_millis(10));
+                server.process_messages().unwrap();
+            }
+        };
+
+        send(&ClientMessage::Join {
+            name: None,
+            token: None,
+        });
+        wait_for_players(&mut server, 1);
+        let player_id = server.players.keys().next().unwrap().clone();
+
+        send(&ClientMessage::Ping {
+            sent_at: Timestamp::now(),
+        });
+        wait_for_players(&mut server, 0);
+        assert!(!server.players.contains_key(&player_id));
+    }
+
+    #[test]
+    fn test_events_in_tick_range() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+
+        server
+            .handle_player_join("player1", None, None)
+            .expect("Join failed");
+        server.current_tick = 10;
+        server
+            .handle_player_join("player2", None, None)
+            .expect("Join failed");
+        server.current_tick = 20;
+        server.remove_player("player1").expect("Remove failed");
+
+        let events = server.events_in(5..=20);
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    tick: 10,
+                    kind: EventKind::PlayerJoined {
+                        player_id: "player2".to_string()
+                    },
+                },
+                Event {
+                    tick: 20,
+                    kind: EventKind::PlayerLeft {
+                        player_id: "player1".to_string()
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spawns_respect_min_separation() {
+        let rules = GameRules {
+            spawn_strategy: SpawnStrategy::Random,
+            min_spawn_separation: 20.0,
+            rng_seed: Some(7),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+
+        for i in 0..10 {
+            server
+                .handle_player_join(&format!("player{}", i), None, None)
+                .expect("Join failed");
+        }
+
+        let positions: Vec<Vector2> = server
+            .game_state
+            .players
+            .values()
+            .map(|p| p.position)
+            .collect();
+        assert_eq!(positions.len(), 10);
+        for i in 0..positions.len() {
+            for j in (i + 1)..positions.len() {
+                let dx = positions[i].x - positions[j].x;
+                let dy = positions[i].y - positions[j].y;
+                assert!(
+                    (dx * dx + dy * dy).sqrt() >= 20.0,
+                    "Players {} and {} spawned too close",
+                    i,
+                    j
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_metadata_reflected_in_snapshot() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server
+            .handle_player_join("player1", None, None)
+            .expect("Join failed");
+        next_message(&receiver); // JoinAccepted
+        next_message(&receiver); // WorldInfo
+
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::SetMeta {
+                    key: "skin".to_string(),
+                    value: "red".to_string(),
+                },
+            )
+            .expect("SetMeta failed");
+        server.broadcast_state(1).expect("Broadcast failed");
+
+        match next_message(&receiver) {
+            ServerMessage::GameState(update) => {
+                let metadata = &update.players["player1"].metadata;
+                assert_eq!(metadata.get("skin").map(String::as_str), Some("red"));
+            }
+            other => panic!("Expected GameState, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_metadata_limits() {
+        let rules = GameRules {
+            max_metadata_keys: 1,
+            max_metadata_len: 4,
+            ..Default::default()
+        };
+        let mut state = GameState::with_rules(rules);
+        state.add_player("player1".to_string(), PlayerState::default());
+
+        assert!(state
+            .set_metadata("player1", "skin".into(), "red".into())
+            .is_ok());
+        assert!(state
+            .set_metadata("player1", "skin".into(), "blue".into())
+            .is_ok());
+        assert!(state
+            .set_metadata("player1", "hat".into(), "cap".into())
+            .is_err());
+        assert!(state
+            .set_metadata("player1", "skin".into(), "purple".into())
+            .is_err());
+    }
+
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> std::result::Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("cannot serialize"))
+        }
+    }
+
+    #[test]
+    fn test_broadcast_serialization_failure_is_not_fatal() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+
+        assert!(server.broadcast(&Unserializable).is_ok());
+        assert!(receiver.try_recv().is_err(), "Nothing should be sent");
+        assert!(server.players.contains_key("player1"));
+
+        // Later ticks still broadcast normally
+        server.run_for(1).expect("Game loop should keep running");
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::GameState(_)
+        ));
+    }
+
+    #[test]
+    fn test_broadcast_records_message_sizes() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+
+        // A 100-byte payload serializes to just over 100 bytes with quotes and newline
+        server.broadcast(&"x".repeat(100)).unwrap();
+        server.broadcast(&"x".repeat(1000)).unwrap();
+        server.broadcast(&"x".repeat(10_000)).unwrap();
+
+        assert_eq!(server.stats().message_sizes.counts(), &[1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_chat_rate_limited_per_player() {
+        let rules = GameRules {
+            chat_limit: 5,
+            chat_interval: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, spammer) = std::sync::mpsc::channel();
+        let (observer_sender, observer) = std::sync::mpsc::channel();
+        server.add_connection("spammer".to_string(), sender);
+        server.add_connection("observer".to_string(), observer_sender);
+        server.handle_player_join("spammer", None, None).unwrap();
+        spammer.try_iter().count(); // Join replies
+
+        for i in 0..20 {
+            server
+                .handle_client_message(
+                    "spammer",
+                    ClientMessage::Chat {
+                        text: format!("spam {}", i),
+                    },
+                )
+                .unwrap();
+        }
+
+        let relayed = observer
+            .try_iter()
+            .map(|bytes| serde_json::from_slice::<ServerMessage>(&bytes).unwrap())
+            .filter(|message| matches!(message, ServerMessage::ChatMessage { .. }))
+            .count();
+        assert_eq!(relayed, 5);
+
+        let rate_limited = spammer
+            .try_iter()
+            .map(|bytes| serde_json::from_slice::<ServerMessage>(&bytes).unwrap())
+            .filter(|message| {
+                matches!(
+                    message,
+                    ServerMessage::Error {
+                        error_code: ErrorCode::RateLimited,
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(rate_limited, 15);
+    }
+
+    #[test]
+    fn test_spectator_snapshots_at_lower_rate() {
+        let mut server = GameServerBuilder::new("127.0.0.1:0")
+            .tick_rate(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        server.set_spectator_rate(Duration::from_millis(500));
+        let (sender, player) = std::sync::mpsc::channel();
+        let (spectator_sender, spectator) = std::sync::mpsc::channel();
+        server.add_connection("player".to_string(), sender);
+        server.add_connection("spectator".to_string(), spectator_sender);
+        server.handle_player_join("player", None, None).unwrap();
+        server
+            .handle_client_message("spectator", ClientMessage::Spectate)
+            .unwrap();
+
+        match next_message(&spectator) {
+            ServerMessage::WorldInfo(info) => assert_eq!(info.broadcast_rate_ms, 500),
+            other => panic!("Expected WorldInfo, got {:?}", other),
+        }
+
+        // One second of 50ms ticks
+        for _ in 0..20 {
+            server.run_tick(Duration::from_millis(50)).unwrap();
+        }
+
+        let snapshots = |receiver: &Receiver<Vec<u8>>| {
+            receiver
+                .try_iter()
+                .map(|bytes| serde_json::from_slice::<ServerMessage>(&bytes).unwrap())
+                .filter(|message| matches!(message, ServerMessage::GameState(_)))
+                .count()
+        };
+        assert_eq!(snapshots(&player), 20);
+        assert_eq!(snapshots(&spectator), 2);
+    }
+
+    #[test]
+    fn test_slots_reused_after_leave() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_slot_encoding(true);
+        let (sender, observer) = std::sync::mpsc::channel();
+        server.add_connection("observer".to_string(), sender);
+        server.handle_player_join("observer", None, None).unwrap();
+        let mut receivers = Vec::new();
+        for id in ["first", "second", "third"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            receivers.push(receiver);
+        }
+        server.handle_player_join("first", None, None).unwrap();
+        server.handle_player_join("second", None, None).unwrap();
+        assert_eq!(server.slot_of("first"), Some(1));
+
+        server
+            .handle_client_message("first", ClientMessage::Disconnect)
+            .unwrap();
+        server.handle_player_join("third", None, None).unwrap();
+        assert_eq!(server.slot_of("third"), Some(1), "Freed slot is reused");
+        assert_eq!(server.slot_of("first"), None);
+
+        server.broadcast_state(1).unwrap();
+        let messages: Vec<ServerMessage> = observer
+            .try_iter()
+            .map(|bytes| serde_json::from_slice(&bytes).unwrap())
+            .collect();
+
+        assert!(messages
+            .iter()
+            .any(|message| matches!(message, ServerMessage::SlotReleased { slot: 1 })));
+        assert!(messages.iter().any(|message| matches!(
+            message,
+            ServerMessage::SlotAssigned { slot: 1, player_id } if player_id == "third"
+        )));
+        match messages.last() {
+            Some(ServerMessage::SlotSnapshot(snapshot)) => {
+                let slots: Vec<u32> = snapshot.players.iter().map(|(slot, _)| *slot).collect();
+                assert_eq!(slots, vec![0, 1, 2]);
+            }
+            other => panic!("Expected SlotSnapshot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_slot_snapshots_split_and_bounded_like_game_state() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_slot_encoding(true);
+        server.set_max_players_per_update(Some(2));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("observer".to_string(), sender);
+        server.handle_player_join("observer", None, None).unwrap();
+        for _ in 0..4 {
+            server.add_bot(BotBehavior::Wander).unwrap();
+        }
+        receiver.try_iter().count();
+
+        server.broadcast_state(1).unwrap();
+        let snapshots: Vec<SlotSnapshot> = receiver
+            .try_iter()
+            .filter_map(|bytes| match serde_json::from_slice(&bytes).unwrap() {
+                ServerMessage::SlotSnapshot(snapshot) => Some(snapshot),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(snapshots.len(), 3);
+        for (part, snapshot) in snapshots.iter().enumerate() {
+            assert_eq!((snapshot.part, snapshot.of), (part as u32, 3));
+            assert!(snapshot.players.len() <= 2);
+            assert_eq!(snapshot.bounds.is_some(), part == 0);
+        }
+        let total: usize = snapshots.iter().map(|s| s.players.len()).sum();
+        assert_eq!(total, 5);
+    }
+
+    #[test]
+    fn test_builder_applies_every_option() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let rules = GameRules {
+            max_velocity: 3.0,
+            ..Default::default()
+        };
+        let server = GameServerBuilder::new("unused")
+            .listener(listener)
+            .rules(rules)
+            .max_players(Some(4))
+            .tick_rate(Duration::from_millis(50))
+            .write_timeout(Some(Duration::from_secs(1)))
+            .read_deadline(None)
+            .max_connections(8)
+            .max_players_per_update(Some(10))
+            .on_tick(|_, _| {})
+            .slow_phase_fraction(None)
+            .init_data(Some(b"map".to_vec()))
+            .idle_suppression(Some(5))
+            .delta_updates(Some(20))
+            .spectator_rate(Duration::from_secs(1))
+            .slot_encoding(true)
+            .input_ordering(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(server.local_addr(), address);
+        assert_eq!(server.game_state.rules().max_velocity, 3.0);
+        assert_eq!(server.game_state.rules().max_players, Some(4));
+        assert_eq!(server.tick_rate(), Duration::from_millis(50));
+        assert_eq!(
+            server.network_config.write_timeout,
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(server.network_config.read_deadline, None);
+        assert_eq!(server.network_config.max_connections, 8);
+        assert_eq!(server.max_players_per_update, Some(10));
+        assert!(server.on_tick.is_some());
+        assert_eq!(server.slow_phase_fraction, None);
+        assert_eq!(server.init_data.as_deref(), Some(&b"map"[..]));
+        assert_eq!(server.idle_keepalive_ticks, Some(5));
+        assert_eq!(server.delta_keyframe_ticks, Some(20));
+        assert_eq!(server.spectator_rate(), Duration::from_secs(1));
+        assert!(server.slot_encoding);
+        assert!(server.input_ordering);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_options() {
+        let build = |builder: GameServerBuilder| builder.build().map(|_| ());
+        assert!(build(GameServerBuilder::new("127.0.0.1:0")).is_ok());
+        assert!(build(GameServerBuilder::new("127.0.0.1:0").tick_rate(Duration::ZERO)).is_err());
+        assert!(build(GameServerBuilder::new("127.0.0.1:0").max_connections(0)).is_err());
+        assert!(build(GameServerBuilder::new("127.0.0.1:0").max_players(Some(0))).is_err());
+    }
+
+    #[test]
+    fn test_throttle_scales_normalized_direction() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+
+        let steer = |server: &mut GameServer, throttle: Option<f32>| {
+            server
+                .handle_client_message(
+                    "player1",
+                    ClientMessage::Move {
+                        direction: Vector2 { x: 30.0, y: 40.0 },
+                        client_time: None,
+                        throttle,
+                    },
+                )
+                .unwrap();
+            server
+                .game_state
+                .get_player_state("player1")
+                .unwrap()
+                .velocity
+        };
+
+        // Half of max_velocity 10 along (0.6, 0.8)
+        let velocity = steer(&mut server, Some(0.5));
+        assert!(velocity.approx_eq(&Vector2 { x: 3.0, y: 4.0 }));
+
+        // Out of range throttles are clamped
+        let velocity = steer(&mut server, Some(2.0));
+        assert!(velocity.approx_eq(&Vector2 { x: 6.0, y: 8.0 }));
+
+        // Without a throttle the direction is the velocity
+        let velocity = steer(&mut server, None);
+        assert_eq!((velocity.x, velocity.y), (30.0, 40.0));
+    }
+
+    #[test]
+    fn test_non_finite_direction_stops_player() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+
+        // 1e39 overflows f32 and parses as infinity
+        let direction: Vector2 = serde_json::from_str(r#"{"x":1e39,"y":0}"#).unwrap();
+        assert!(direction.x.is_infinite());
+        for throttle in [Some(1.0), None] {
+            server
+                .inject(
+                    "player1",
+                    ClientMessage::Move {
+                        direction,
+                        client_time: None,
+                        throttle,
+                    },
+                )
+                .unwrap();
+            server.update_game_state(Duration::from_millis(16)).unwrap();
+
+            let player = server.game_state.get_player_state("player1").unwrap();
+            assert_eq!(player.velocity, Vector2::default());
+            assert!(player.position.is_finite());
+        }
+    }
+
+    #[test]
+    fn test_eliminated_player_announced_and_can_rejoin() {
+        let rules = GameRules {
+            boundary_mode: BoundaryMode::Eliminate,
+            ..Default::default()
+        };
+        let edge = rules.map_bounds.0;
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+        receiver.try_iter().count();
+
+        let player = server.game_state.players.get_mut("player1").unwrap();
+        player.position = Vector2 { x: edge, y: 0.0 };
+        player.velocity = Vector2 { x: -10.0, y: 0.0 };
+        server.update_game_state(Duration::from_millis(16)).unwrap();
+
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::PlayerLeft { player_id } if player_id == "player1"
+        ));
+        assert!(matches!(
+            server
+                .event_log
+                .in_range(..)
+                .last()
+                .map(|event| &event.kind),
+            Some(EventKind::Eliminated { .. })
+        ));
+
+        // The connection stays open, so the player can join the next round
+        server.handle_player_join("player1", None, None).unwrap();
+        assert!(server.game_state.get_player_state("player1").is_some());
+    }
+
+    #[test]
+    fn test_stopped_input_decays_velocity() {
+        let rules = GameRules {
+            input_timeout: Some(Duration::from_millis(100)),
+            stop_deceleration: 50.0,
+            enable_collisions: false,
+            spawn_grace: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let mut receivers = Vec::new();
+        for player_id in ["mover", "stopper"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(player_id.to_string(), sender);
+            server.handle_player_join(player_id, None, None).unwrap();
+            receivers.push(receiver);
+        }
+
+        let direction = Vector2 { x: 5.0, y: 0.0 };
+        server.apply_move("stopper", direction, None);
+        for _ in 0..10 {
+            server.apply_move("mover", direction, None);
+            server.game_state.update(Duration::from_millis(50)).unwrap();
+        }
+
+        let state = &server.game_state;
+        assert!(state.has_active_input("mover"));
+        assert_eq!(state.get_player_state("mover").unwrap().velocity, direction);
+        assert!(!state.has_active_input("stopper"));
+        assert_eq!(
+            state.get_player_state("stopper").unwrap().velocity,
+            Vector2::default()
+        );
+    }
+
+    #[test]
+    fn test_slow_broadcast_thread_keeps_tick_rate() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let delivered = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = delivered.clone();
+        server.broadcaster = Some(Broadcaster::with_deliver(move |_, bytes| {
+            std::thread::sleep(Duration::from_millis(50));
+            log.lock().unwrap().push(bytes);
+        }));
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+
+        // Sending inline would take at least 20 * 50ms
+        let started = Instant::now();
+        for _ in 0..20 {
+            server.step(Duration::from_millis(16)).unwrap();
+        }
+        assert!(
+            started.elapsed() < Duration::from_millis(500),
+            "Ticks took {:?}",
+            started.elapsed()
+        );
+
+        // Dropping waits for the last job; ticks it fell behind on were skipped
+        drop(server.broadcaster.take());
+        let delivered = delivered.lock().unwrap();
+        assert!(delivered.len() < 20, "Sent {} snapshots", delivered.len());
+        let last: ServerMessage = serde_json::from_slice(delivered.last().unwrap()).unwrap();
+        assert!(matches!(last, ServerMessage::GameState(update) if update.tick == 19));
+    }
+
+    #[test]
+    fn test_broadcast_thread_keeps_deltas_and_leaves_in_order() {
+        let mut server = GameServerBuilder::new("127.0.0.1:0")
+            .delta_updates(Some(1_000))
+            .build()
+            .unwrap();
+        server.broadcaster = Some(Broadcaster::with_deliver(|sender, bytes| {
+            std::thread::sleep(Duration::from_millis(20));
+            let _ = sender.send(bytes);
+        }));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("p1".to_string(), sender);
+        let (sender, _p2_receiver) = std::sync::mpsc::channel();
+        server.add_connection("p2".to_string(), sender);
+        for id in ["p1", "p2"] {
+            server.handle_player_join(id, None, None).unwrap();
+            server.game_state.players.get_mut(id).unwrap().velocity = Vector2 { x: 1.0, y: 1.0 };
+        }
+
+        for tick in 0..20 {
+            if tick == 10 {
+                server.remove_player("p2").unwrap();
+            }
+            server.step(Duration::from_millis(16)).unwrap();
+            // Falls behind until the last few ticks, then keeps up
+            if tick >= 15 {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+        drop(server.broadcaster.take());
+
+        // Each delta builds on the state sent just before it, and nothing brings p2
+        // back once it has left
+        let (mut last_tick, mut left, mut deltas) = (None, false, 0);
+        for bytes in receiver.try_iter() {
+            match serde_json::from_slice(&bytes).unwrap() {
+                ServerMessage::GameState(update) => {
+                    assert!(!(left && update.players.contains_key("p2")));
+                    last_tick = Some(update.tick);
+                }
+                ServerMessage::GameStateDelta(delta) => {
+                    assert_eq!(Some(delta.base_tick), last_tick);
+                    assert!(!(left && delta.changed.contains_key("p2")));
+                    last_tick = Some(delta.tick);
+                    deltas += 1;
+                }
+                ServerMessage::PlayerLeft { player_id } if player_id == "p2" => left = true,
+                _ => {}
+            }
+        }
+        assert!(left);
+        assert!(deltas > 0, "Deltas resume once the thread keeps up");
+        assert_eq!(last_tick, Some(19));
+    }
+
+    #[test]
+    fn test_snapshot_field_allowlist_trims_broadcasts() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+        receiver.try_iter().count();
+
+        server.set_snapshot_fields(Some(HashSet::from([SnapshotField::Position])));
+        server.broadcast_state(1).unwrap();
+
+        let json = String::from_utf8(receiver.try_recv().unwrap()).unwrap();
+        assert!(json.contains("\"position\""), "{}", json);
+        assert!(!json.contains("velocity"), "{}", json);
+        assert!(!json.contains("status"), "{}", json);
+
+        server.set_snapshot_fields(None);
+        server.broadcast_state(2).unwrap();
+        let json = String::from_utf8(receiver.try_recv().unwrap()).unwrap();
+        assert!(
+            json.contains("velocity") && json.contains("status"),
+            "{}",
+            json
+        );
+
+        // Deltas between keyframes are trimmed the same way
+        server.set_snapshot_fields(Some(HashSet::from([SnapshotField::Position])));
+        server.set_delta_updates(Some(10));
+        for tick in 3..6 {
+            server.apply_move("player1", Vector2 { x: 1.0, y: 0.0 }, None);
+            server.update_game_state(Duration::from_millis(16)).unwrap();
+            server.broadcast_state(tick).unwrap();
+        }
+        let lines: Vec<String> = receiver
+            .try_iter()
+            .map(|bytes| String::from_utf8(bytes).unwrap())
+            .collect();
+        assert!(lines.iter().any(|json| json.contains("GameStateDelta")));
+        assert!(
+            lines.iter().all(|json| !json.contains("velocity")),
+            "{:?}",
+            lines
+        );
+    }
+
+    #[test]
+    fn test_disconnected_player_reaped_after_grace() {
+        let rules = GameRules {
+            reconnect_grace: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let mut receivers = Vec::new();
+        for id in ["player1", "watcher"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            receivers.push(receiver);
+        }
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::Join {
+                    name: None,
+                    token: Some("abc".into()),
+                },
+            )
+            .unwrap();
+        server.handle_player_join("watcher", None, None).unwrap();
+        server
+            .handle_client_message("player1", ClientMessage::Disconnect)
+            .unwrap();
+        receivers[1].try_iter().count();
+
+        // Still in the world until the grace period has fully elapsed
+        let left_at = server.retained["player1"].as_millis();
+        server
+            .reap_disconnected(&Timestamp::from_millis(left_at + 4_999))
+            .unwrap();
+        assert!(server.game_state.get_player_state("player1").is_some());
+        assert!(receivers[1].try_recv().is_err());
+
+        server
+            .reap_disconnected(&Timestamp::from_millis(left_at + 5_000))
+            .unwrap();
+        assert!(server.game_state.get_player_state("player1").is_none());
+        assert!(server.retained.is_empty());
+        assert!(matches!(
+            next_message(&receivers[1]),
+            ServerMessage::PlayerLeft { player_id } if player_id == "player1"
+        ));
+    }
+
+    #[test]
+    fn test_token_reconnect_resumes_retained_player() {
+        let rules = GameRules {
+            reconnect_grace: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        server.set_slot_encoding(true);
+        let join = |name: Option<&str>| ClientMessage::Join {
+            name: name.map(String::from),
+            token: Some("abc".into()),
+        };
+        let mut receivers = HashMap::new();
+        for id in ["old", "watcher", "new"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            receivers.insert(id, receiver);
+        }
+        server
+            .handle_client_message("old", join(Some("Alice")))
+            .unwrap();
+        server.handle_player_join("watcher", None, None).unwrap();
+        let position = Vector2 { x: 12.0, y: -7.0 };
+        server.game_state.players.get_mut("old").unwrap().position = position;
+        let slot = server.slots.get("old");
+        server
+            .handle_client_message("old", ClientMessage::Disconnect)
+            .unwrap();
+        receivers["watcher"].try_iter().count();
+        receivers["new"].try_iter().count();
+
+        server.handle_client_message("new", join(None)).unwrap();
+        assert!(matches!(
+            next_message(&receivers["new"]),
+            ServerMessage::JoinAccepted { player_id, name }
+                if player_id == "new" && name.as_deref() == Some("Alice")
+        ));
+        let player = server.game_state.get_player_state("new").unwrap();
+        assert_eq!(player.position, position);
+        assert_eq!(player.name.as_deref(), Some("Alice"));
+        assert!(server.game_state.get_player_state("old").is_none());
+        assert_eq!(server.game_state.get_player_count(), 2);
+        assert!(server.retained.is_empty());
+        assert_eq!(server.slots.get("new"), slot);
+        assert_eq!(server.identities["abc"], "new");
+
+        // Others see the slot change hands, not a leave and a fresh spawn
+        let seen: Vec<ServerMessage> = receivers["watcher"]
+            .try_iter()
+            .map(|bytes| serde_json::from_slice(&bytes).unwrap())
+            .collect();
+        assert!(!seen
+            .iter()
+            .any(|message| matches!(message, ServerMessage::PlayerLeft { .. })));
+        assert!(seen.iter().any(|message| matches!(
+            message,
+            ServerMessage::SlotAssigned { slot: assigned, player_id }
+                if Some(*assigned) == slot && player_id == "new"
+        )));
+    }
+
+    #[test]
+    fn test_failed_send_keeps_reconnect_grace() {
+        let rules = GameRules {
+            reconnect_grace: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::Join {
+                    name: None,
+                    token: Some("abc".into()),
+                },
+            )
+            .unwrap();
+
+        // The connection dies and a broadcast notices before the reader does
+        drop(receiver);
+        server.step(Duration::from_millis(16)).unwrap();
+        assert!(!server.players.contains_key("player1"));
+        assert!(server.retained.contains_key("player1"));
+        assert!(server.game_state.get_player_state("player1").is_some());
+
+        // The reader's late Disconnect changes nothing
+        server.handle_disconnect("player1").unwrap();
+        assert!(server.retained.contains_key("player1"));
+    }
+
+    #[test]
+    fn test_rewind_clamps_to_history_ticks() {
+        let rules = GameRules {
+            history_ticks: 4,
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+        server
+            .game_state
+            .players
+            .get_mut("player1")
+            .unwrap()
+            .velocity = Vector2 { x: 10.0, y: 0.0 };
+
+        for _ in 0..10 {
+            server.step(Duration::from_millis(100)).unwrap();
+        }
+
+        // Ticks 6-9 are kept; older requests get tick 6 instead of failing
+        let (tick, position) = server.rewind("player1", 0).unwrap();
+        assert_eq!(tick, 6);
+        assert!(approx_eq_f32(position.x, 7.0, EPSILON));
+
+        let (tick, _) = server.rewind("player1", 8).unwrap();
+        assert_eq!(tick, 8);
+    }
+
+    #[test]
+    fn test_players_snapshot_has_full_state() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+        server
+            .game_state
+            .set_metadata("player1", "team".to_string(), "red".to_string())
+            .unwrap();
+        server
+            .game_state
+            .players
+            .get_mut("player1")
+            .unwrap()
+            .velocity = Vector2 { x: 2.0, y: -1.0 };
+        let bot_id = server.add_bot(BotBehavior::Wander).unwrap();
+
+        let snapshot = server.players_snapshot();
+        assert_eq!(snapshot.len(), 2);
+        let player = &snapshot["player1"];
+        assert_eq!((player.velocity.x, player.velocity.y), (2.0, -1.0));
+        assert_eq!(player.status, PlayerStatus::SpawnProtected);
+        assert_eq!(player.metadata["team"], "red");
+        assert_eq!(snapshot[&bot_id].name.as_deref(), Some("Bot 1"));
+
+        // Detached: later ticks don't change it
+        server.step(Duration::from_millis(16)).unwrap();
+        assert_eq!(snapshot["player1"].position.x, 0.0);
+    }
+
     #[test]
-    fn test_game_state_update() {
-        let mut state = GameState::new();
-        let player_id = "test_player".to_string();
-        let initial_state = PlayerState {
-            position: Vector2 { x: 0.0, y: 0.0 },
-            velocity: Vector2 { x: 1.0, y: 0.0 },
-            last_update: Timestamp::now(),
+    fn test_position_reports_need_client_authority() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let mut receivers = Vec::new();
+        for id in ["simulated", "projectile"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            receivers.push(receiver);
+            server.add_connection(id.to_string(), sender);
+            server.handle_player_join(id, None, None).unwrap();
+        }
+        server
+            .game_state
+            .set_client_authoritative("projectile", true)
+            .unwrap();
+
+        // Backdates the last report, so any distance is within reach
+        let wait = |server: &mut GameServer| {
+            let player = server.game_state.players.get_mut("projectile").unwrap();
+            player.last_update = Timestamp::default();
+        };
+        let report = |server: &mut GameServer, id: &str, x: f32| {
+            server
+                .handle_client_message(
+                    id,
+                    ClientMessage::ReportPosition {
+                        position: Vector2 { x, y: 5.0 },
+                    },
+                )
+                .unwrap();
+            server.game_state.get_player_state(id).unwrap().position
         };
 
-        // Add player
-        state.add_player(player_id.clone(), initial_state);
-        assert_eq!(state.get_player_count(), 1);
+        let position = report(&mut server, "simulated", 50.0);
+        assert_eq!((position.x, position.y), (0.0, 0.0), "Server-authoritative");
 
-        // Update game state
-        let delta = Duration::from_millis(100); // 100ms
-        state.update(delta).expect("Update failed");
+        wait(&mut server);
+        let position = report(&mut server, "projectile", 50.0);
+        assert_eq!((position.x, position.y), (50.0, 5.0));
 
-        // Check position updated according to velocity
-        if let Some(updated_state) = state.get_player_state(&player_id) {
-            assert!(
-                updated_state.position.x > 0.0,
-                "Player should have moved right"
-            );
-            assert_eq!(
-                updated_state.position.y, 0.0,
-                "Player should not have moved vertically"
-            );
-        } else {
-            panic!("Player state not found");
+        // Faster than max speed allows is dropped, a plausible step is taken
+        assert_eq!(report(&mut server, "projectile", 90.0).x, 50.0);
+        assert_eq!(report(&mut server, "projectile", 51.0).x, 51.0);
+
+        // Bounded to the map, and implausible values are dropped
+        wait(&mut server);
+        assert_eq!(report(&mut server, "projectile", 1e6).x, 100.0);
+        wait(&mut server);
+        assert_eq!(report(&mut server, "projectile", f32::NAN).x, 100.0);
+        assert_eq!(report(&mut server, "projectile", f32::INFINITY).x, 100.0);
+
+        // The server no longer moves it by velocity
+        server
+            .game_state
+            .players
+            .get_mut("projectile")
+            .unwrap()
+            .velocity = Vector2 { x: -5.0, y: 0.0 };
+        server.step(Duration::from_millis(100)).unwrap();
+        assert_eq!(
+            server
+                .game_state
+                .get_player_state("projectile")
+                .unwrap()
+                .position
+                .x,
+            100.0
+        );
+    }
+
+    #[test]
+    fn test_move_before_join_rejected() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("early".to_string(), sender);
+
+        server
+            .handle_client_message(
+                "early",
+                ClientMessage::Move {
+                    direction: Vector2 { x: 1.0, y: 0.0 },
+                    client_time: None,
+                    throttle: None,
+                },
+            )
+            .unwrap();
+
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::Error {
+                error_code: ErrorCode::ProtocolError,
+                ..
+            }
+        ));
+        assert!(server.game_state.get_player_state("early").is_none());
+
+        server.handle_player_join("early", None, None).unwrap();
+        receiver.try_iter().count(); // Join replies
+        server
+            .handle_client_message(
+                "early",
+                ClientMessage::Join {
+                    name: None,
+                    token: None,
+                },
+            )
+            .unwrap();
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::Error {
+                error_code: ErrorCode::ProtocolError,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_batched_moves_queued_as_separate_inputs() {
+        let mut server = GameServerBuilder::new("127.0.0.1:0")
+            .input_ordering(true)
+            .build()
+            .unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+
+        let moves = (1..=3)
+            .map(|i| ClientMessage::Move {
+                direction: Vector2 {
+                    x: i as f32,
+                    y: 0.0,
+                },
+                client_time: Some(100 * i),
+                throttle: None,
+            })
+            .collect();
+        server
+            .handle_client_message("player1", ClientMessage::Batch { messages: moves })
+            .unwrap();
+
+        let queued: Vec<u64> = server.players["player1"]
+            .input_queue
+            .iter()
+            .map(|input| match input.message {
+                ClientMessage::Move { client_time, .. } => client_time.unwrap(),
+                _ => panic!("Expected only moves"),
+            })
+            .collect();
+        assert_eq!(queued, vec![100, 200, 300]);
+
+        server.apply_queued_inputs();
+        assert_eq!(server.game_state.players["player1"].velocity.x, 3.0);
+    }
+
+    #[test]
+    fn test_reliable_move_before_join_acked_then_rejected() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("early".to_string(), sender);
+
+        server
+            .handle_client_message(
+                "early",
+                ClientMessage::Reliable {
+                    seq: 1,
+                    message: Box::new(ClientMessage::Move {
+                        direction: Vector2 { x: 1.0, y: 0.0 },
+                        client_time: None,
+                        throttle: None,
+                    }),
+                },
+            )
+            .unwrap();
+
+        // The wrapper is delivered, but the Move inside it still needs a Join
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::Ack { seq: 1 }
+        ));
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::Error {
+                error_code: ErrorCode::ProtocolError,
+                ..
+            }
+        ));
+        assert!(server.players.get("early").unwrap().input_queue.is_empty());
+    }
+
+    #[test]
+    fn test_join_validator_rejects_by_name() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_join_validator(|request| match request.name {
+            Some("Mallory") => Err("Mallory is banned".into()),
+            _ => Ok(()),
+        });
+        let mut receivers = HashMap::new();
+        for (id, name) in [("127.0.0.1:5001", "Mallory"), ("127.0.0.1:5002", "Alice")] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            receivers.insert(id, receiver);
+            server
+                .handle_client_message(
+                    id,
+                    ClientMessage::Join {
+                        name: Some(name.into()),
+                        token: None,
+                    },
+                )
+                .unwrap();
         }
+
+        assert!(matches!(
+            next_message(&receivers["127.0.0.1:5001"]),
+            ServerMessage::Error {
+                error_code: ErrorCode::JoinRejected,
+                ref message,
+            } if message == "Mallory is banned"
+        ));
+        assert!(server
+            .game_state
+            .get_player_state("127.0.0.1:5001")
+            .is_none());
+        assert!(!server.players.contains_key("127.0.0.1:5001"));
+
+        assert!(matches!(
+            next_message(&receivers["127.0.0.1:5002"]),
+            ServerMessage::JoinAccepted { .. }
+        ));
     }
 
     #[test]
-    fn test_collision_detection() {
-        let mut state = GameState::new();
-        let rules = GameRules::default();
+    fn test_duplicate_names_follow_policy() {
+        let join_as = |server: &mut GameServer, id: &str| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            server
+                .handle_player_join(id, Some("Alice".to_string()), None)
+                .unwrap();
+            (next_message(&receiver), receiver)
+        };
 
-        // Add two players close to each other
-        let player1 = PlayerState {
-            position: Vector2 { x: 0.0, y: 0.0 },
-            velocity: Vector2 { x: 1.0, y: 0.0 },
-            last_update: Timestamp::now(),
+        let rules = GameRules {
+            name_policy: NamePolicy::Reject,
+            ..Default::default()
         };
-        let player2 = PlayerState {
-            position: Vector2 {
-                x: rules.collision_radius - 1.0,
-                y: 0.0,
-            },
-            velocity: Vector2 { x: -1.0, y: 0.0 },
-            last_update: Timestamp::now(),
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let _first = join_as(&mut server, "first");
+        match join_as(&mut server, "second").0 {
+            ServerMessage::Error { error_code, .. } => {
+                assert_eq!(error_code, ErrorCode::InvalidName)
+            }
+            other => panic!("Expected Error, got {:?}", other),
+        }
+        assert!(server.game_state.get_player_state("second").is_none());
+
+        let rules = GameRules {
+            name_policy: NamePolicy::Suffix,
+            ..Default::default()
         };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let _first = join_as(&mut server, "first");
+        match join_as(&mut server, "second").0 {
+            ServerMessage::JoinAccepted { name, .. } => {
+                assert_eq!(name.as_deref(), Some("Alice 2"));
+                assert!(validate_name(name.as_deref().unwrap()).is_ok());
+            }
+            other => panic!("Expected JoinAccepted, got {:?}", other),
+        }
+        match join_as(&mut server, "third").0 {
+            ServerMessage::JoinAccepted { name, .. } => {
+                assert_eq!(name.as_deref(), Some("Alice 3"))
+            }
+            other => panic!("Expected JoinAccepted, got {:?}", other),
+        }
+    }
 
-        state.add_player("player1".to_string(), player1);
-        state.add_player("player2".to_string(), player2);
+    #[test]
+    fn test_disconnect_all_removes_everyone_with_reason() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let mut receivers = Vec::new();
+        for id in ["player1", "player2"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            server.handle_player_join(id, None, None).unwrap();
+            receiver.try_iter().count(); // Join replies
+            receivers.push(receiver);
+        }
 
-        // Update should trigger collision resolution
-        state
-            .update(Duration::from_millis(16))
-            .expect("Update failed");
+        server.disconnect_all("Server restarting").unwrap();
 
-        // Check that players were pushed apart
-        let p1_state = state
+        assert!(server.players.is_empty());
+        assert_eq!(server.game_state.get_player_count(), 0);
+        for receiver in &receivers {
+            match next_message(receiver) {
+                ServerMessage::Error {
+                    error_code,
+                    message,
+                } => {
+                    assert_eq!(error_code, ErrorCode::Kicked);
+                    assert_eq!(message, "Server restarting");
+                }
+                other => panic!("Expected Error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_extreme_client_time_is_clamped() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_input_ordering(true);
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+
+        for client_time in [1 << 63, u64::MAX, 0] {
+            server
+                .inject(
+                    "player1",
+                    ClientMessage::Move {
+                        direction: Vector2 { x: 1.0, y: 0.0 },
+                        client_time: Some(client_time),
+                        throttle: None,
+                    },
+                )
+                .unwrap();
+        }
+        let now = Timestamp::now();
+        for input in &server.players["player1"].input_queue {
+            // Allows for the clock moving on since the inputs arrived
+            assert!(input.timestamp.duration_since(&now) <= MAX_INPUT_TIME_DRIFT);
+            assert!(now.duration_since(&input.timestamp) <= MAX_INPUT_TIME_DRIFT * 2);
+        }
+        server.process_messages().unwrap();
+    }
+
+    #[test]
+    fn test_moves_reordered_by_client_time() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_input_ordering(true);
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+
+        // The later input arrives first
+        for (x, client_time) in [(2.0, 1_100), (1.0, 1_000)] {
+            server
+                .handle_client_message(
+                    "player1",
+                    ClientMessage::Move {
+                        direction: Vector2 { x, y: 0.0 },
+                        client_time: Some(client_time),
+                        throttle: None,
+                    },
+                )
+                .unwrap();
+        }
+        let skew = server.clock_skew("player1").unwrap();
+        assert!(skew > 0, "Server clock is far ahead of client_time");
+
+        server.process_messages().unwrap();
+        let velocity = server
+            .game_state
             .get_player_state("player1")
-            .expect("Player1 not found");
-        let p2_state = state
-            .get_player_state("player2")
-            .expect("Player2 not found");
+            .unwrap()
+            .velocity;
+        assert_eq!(velocity.x, 2.0, "Latest client_time should win");
 
-        let distance = ((p1_state.position.x - p2_state.position.x).powi(2)
-            + (p1_state.position.y - p2_state.position.y).powi(2))
-        .sqrt();
+        // Without ordering the last arrival wins
+        server.set_input_ordering(false);
+        for (x, client_time) in [(2.0, 1_100), (1.0, 1_000)] {
+            server
+                .handle_client_message(
+                    "player1",
+                    ClientMessage::Move {
+                        direction: Vector2 { x, y: 0.0 },
+                        client_time: Some(client_time),
+                        throttle: None,
+                    },
+                )
+                .unwrap();
+        }
+        let velocity = server
+            .game_state
+            .get_player_state("player1")
+            .unwrap()
+            .velocity;
+        assert_eq!(velocity.x, 1.0);
+    }
+
+    #[test]
+    fn test_input_queue_overflow_policies() {
+        let queued_x = |server: &GameServer| -> Vec<f32> {
+            server.players["player1"]
+                .input_queue
+                .iter()
+                .map(|input| match input.message {
+                    ClientMessage::Move { direction, .. } => direction.x,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+
+        for policy in [
+            OverflowPolicy::DropOldest,
+            OverflowPolicy::DropNewest,
+            OverflowPolicy::Disconnect,
+        ] {
+            let rules = GameRules {
+                max_input_queue: 2,
+                input_overflow: policy,
+                ..Default::default()
+            };
+            let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+            server.set_input_ordering(true);
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection("player1".to_string(), sender);
+            server.handle_player_join("player1", None, None).unwrap();
+            receiver.try_iter().count();
+
+            for x in [1.0, 2.0, 3.0] {
+                server
+                    .handle_client_message(
+                        "player1",
+                        ClientMessage::Move {
+                            direction: Vector2 { x, y: 0.0 },
+                            client_time: None,
+                            throttle: None,
+                        },
+                    )
+                    .unwrap();
+            }
+
+            match policy {
+                OverflowPolicy::DropOldest => assert_eq!(queued_x(&server), vec![2.0, 3.0]),
+                OverflowPolicy::DropNewest => assert_eq!(queued_x(&server), vec![1.0, 2.0]),
+                OverflowPolicy::Disconnect => {
+                    assert!(!server.players.contains_key("player1"));
+                    assert!(receiver.try_iter().any(|bytes| matches!(
+                        serde_json::from_slice(&bytes),
+                        Ok(ServerMessage::Error {
+                            error_code: ErrorCode::Kicked,
+                            ..
+                        })
+                    )));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_duplicate_identity_follows_policy() {
+        let join = || ClientMessage::Join {
+            name: None,
+            token: Some("device-1".to_string()),
+        };
+
+        for policy in [DuplicatePolicy::Supersede, DuplicatePolicy::Reject] {
+            let rules = GameRules {
+                duplicate_policy: policy,
+                ..Default::default()
+            };
+            let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+            let (old_sender, old_receiver) = std::sync::mpsc::channel();
+            let (new_sender, new_receiver) = std::sync::mpsc::channel();
+            server.add_connection("old".to_string(), old_sender);
+            server.add_connection("new".to_string(), new_sender);
+
+            server.handle_client_message("old", join()).unwrap();
+            old_receiver.try_iter().count(); // Join replies
+            server.handle_client_message("new", join()).unwrap();
+
+            let is_error = |bytes: Vec<u8>, code: ErrorCode| {
+                matches!(
+                    serde_json::from_slice(&bytes),
+                    Ok(ServerMessage::Error { error_code, .. }) if error_code == code
+                )
+            };
+            match policy {
+                DuplicatePolicy::Supersede => {
+                    assert!(old_receiver
+                        .try_iter()
+                        .any(|bytes| is_error(bytes, ErrorCode::Kicked)));
+                    assert!(server.game_state.get_player_state("old").is_none());
+                    assert!(server.game_state.get_player_state("new").is_some());
+                }
+                DuplicatePolicy::Reject => {
+                    assert!(is_error(
+                        new_receiver.try_recv().unwrap(),
+                        ErrorCode::DuplicateConnection
+                    ));
+                    assert!(server.game_state.get_player_state("old").is_some());
+                    assert!(server.game_state.get_player_state("new").is_none());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejected_join_keeps_identity_holder() {
+        let rules = GameRules {
+            max_players: Some(1),
+            name_policy: NamePolicy::Reject,
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let mut receivers = Vec::new();
+        for id in ["p1", "p2", "p3"] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            server.add_connection(id.to_string(), sender);
+            receivers.push(receiver);
+        }
+        let join = |name: &str| ClientMessage::Join {
+            name: Some(name.to_string()),
+            token: Some("tok".to_string()),
+        };
+        server.handle_client_message("p1", join("Alice")).unwrap();
+
+        // An invalid name fails the join without kicking the holder
+        server.handle_client_message("p2", join("<bad>")).unwrap();
+        assert!(server.game_state.get_player_state("p1").is_some());
+        assert!(server.game_state.get_player_state("p2").is_none());
+        assert_eq!(server.identities["tok"], "p1");
+
+        // A valid one replaces the holder, reusing its name and its place on a full server
+        server.handle_client_message("p3", join("Alice")).unwrap();
+        assert!(server.game_state.get_player_state("p1").is_none());
+        let joined = server.game_state.get_player_state("p3").unwrap();
+        assert_eq!(joined.name.as_deref(), Some("Alice"));
+    }
+
+    #[test]
+    fn test_wandering_bot_moves_without_clients() {
+        let rules = GameRules {
+            rng_seed: Some(3),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let bot_id = server.add_bot(BotBehavior::Wander).unwrap();
+        let start = server
+            .game_state()
+            .get_player_state(&bot_id)
+            .unwrap()
+            .position;
+
+        for _ in 0..10 {
+            server.step(Duration::from_millis(16)).unwrap();
+        }
 
+        let bot = server.game_state().get_player_state(&bot_id).unwrap();
         assert!(
-            distance >= rules.collision_radius,
-            "Players should be pushed apart to at least collision radius"
+            bot.position.distance_squared(&start) > 0.0,
+            "Bot should move"
         );
+        assert_eq!(bot.name.as_deref(), Some("Bot 1"));
+        assert!(server.players.is_empty(), "Bots have no connection");
     }
 
     #[test]
-    fn test_bounds_checking() {
-        let mut state = GameState::new();
-        let rules = GameRules::default();
+    fn test_chasing_bot_closes_distance() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("target".to_string(), sender);
+        server.handle_player_join("target", None, None).unwrap();
+        let bot_id = server.add_bot(BotBehavior::ChaseNearest).unwrap();
+        server.game_state.players.get_mut(&bot_id).unwrap().position = Vector2 { x: 80.0, y: 0.0 };
 
-        // Add player at edge of map with velocity pointing outward
-        let player_state = PlayerState {
-            position: Vector2 {
-                x: rules.map_bounds.1,
-                y: 0.0,
-            },
-            velocity: Vector2 { x: 10.0, y: 0.0 },
-            last_update: Timestamp::now(),
+        server.step(Duration::from_millis(100)).unwrap();
+
+        let bot = server.game_state().get_player_state(&bot_id).unwrap();
+        assert!(bot.position.x < 80.0);
+        assert!(bot.velocity.x < 0.0);
+    }
+
+    #[test]
+    fn test_bots_follow_player_cap_and_name_policy() {
+        let rules = GameRules {
+            max_players: Some(2),
+            name_policy: NamePolicy::Suffix,
+            ..Default::default()
         };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("human".to_string(), sender);
+        server
+            .handle_player_join("human", Some("Bot 1".to_string()), None)
+            .unwrap();
 
-        state.add_player("player1".to_string(), player_state);
-        state
-            .update(Duration::from_millis(16))
-            .expect("Update failed");
+        let bot_id = server.add_bot(BotBehavior::Wander).unwrap();
+        let bot = server.game_state.get_player_state(&bot_id).unwrap();
+        assert_eq!(bot.name.as_deref(), Some("Bot 1 2"));
+        assert!(
+            server.add_bot(BotBehavior::Wander).is_err(),
+            "Server is full"
+        );
 
-        // Check that player position is clamped to map bounds
-        let updated_state = state.get_player_state("player1").expect("Player not found");
+        receiver.try_iter().count();
+        server.remove_bot(&bot_id).unwrap();
+        assert!(server.game_state.get_player_state(&bot_id).is_none());
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::PlayerLeft { player_id } if player_id == bot_id
+        ));
+        assert!(server.remove_bot("human").is_err(), "Only bots");
+
+        // A restart clears bots along with connections
+        server.add_bot(BotBehavior::Wander).unwrap();
+        server.disconnect_all("Restarting").unwrap();
+        assert_eq!(server.game_state.get_player_count(), 0);
+        assert!(server.bots.is_empty());
+    }
+
+    #[test]
+    fn test_headless_ticks_skip_pacing() {
+        let rules = GameRules {
+            headless_delta: Some(Duration::from_millis(16)),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender);
+        server.handle_player_join("player1", None, None).unwrap();
+        server
+            .game_state
+            .players
+            .get_mut("player1")
+            .unwrap()
+            .velocity = Vector2 { x: 5.0, y: 0.0 };
+
+        // 16s of game time at real-time pacing
+        let started = Instant::now();
+        server.run_for(1000).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        assert_eq!(server.current_tick(), 1000);
+        let position = server
+            .game_state()
+            .get_player_state("player1")
+            .unwrap()
+            .position;
+        assert!(approx_eq_f32(position.x, 80.0, 1e-3), "x = {}", position.x);
+    }
+
+    #[test]
+    fn test_first_snapshot_carries_bounds() {
+        let rules = GameRules {
+            map_bounds: (-40.0, 40.0),
+            ..Default::default()
+        };
+        let mut server = GameServer::with_rules("127.0.0.1:0", rules).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("late".to_string(), sender);
+        server.handle_player_join("late", None, None).unwrap();
+        receiver.try_iter().count(); // Join replies
+
+        server.broadcast_state(1).unwrap();
+        match next_message(&receiver) {
+            ServerMessage::GameState(update) => assert_eq!(
+                update.bounds,
+                Some((Vector2 { x: -40.0, y: -40.0 }, Vector2 { x: 40.0, y: 40.0 }))
+            ),
+            other => panic!("Expected GameState, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rename_validated_and_broadcast() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        let (sender1, receiver1) = std::sync::mpsc::channel();
+        let (sender2, receiver2) = std::sync::mpsc::channel();
+        server.add_connection("player1".to_string(), sender1);
+        server.add_connection("player2".to_string(), sender2);
+        server
+            .handle_player_join("player1", Some("Alice".to_string()), None)
+            .expect("Join failed");
+        server
+            .handle_player_join("player2", None, None)
+            .expect("Join failed");
+        for receiver in [&receiver1, &receiver2] {
+            next_message(receiver); // JoinAccepted
+            next_message(receiver); // WorldInfo
+        }
+
+        // Too long: rejected to the sender only
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::SetName {
+                    name: "A".repeat(MAX_NAME_LEN + 1),
+                },
+            )
+            .expect("Rename failed");
+        match next_message(&receiver1) {
+            ServerMessage::Error { error_code, .. } => {
+                assert_eq!(error_code, ErrorCode::InvalidName)
+            }
+            other => panic!("Expected Error, got {:?}", other),
+        }
+        assert!(receiver2.try_recv().is_err());
+        assert_eq!(
+            server.game_state.get_player_state("player1").unwrap().name,
+            Some("Alice".to_string())
+        );
+
+        // Valid: applied and broadcast to everyone
+        server
+            .handle_client_message(
+                "player1",
+                ClientMessage::SetName {
+                    name: "Alicia".to_string(),
+                },
+            )
+            .expect("Rename failed");
+        match next_message(&receiver2) {
+            ServerMessage::PlayerRenamed { player_id, name } => {
+                assert_eq!(player_id, "player1");
+                assert_eq!(name, "Alicia");
+            }
+            other => panic!("Expected PlayerRenamed, got {:?}", other),
+        }
+        assert_eq!(
+            server.game_state.get_player_state("player1").unwrap().name,
+            Some("Alicia".to_string())
+        );
+    }
+
+    #[test]
+    fn test_idle_world_suppresses_snapshots() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_idle_suppression(Some(3));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("observer".to_string(), sender);
+        server
+            .game_state
+            .add_player("player1".to_string(), PlayerState::default());
+
+        let sent = |server: &mut GameServer, tick: u64| {
+            server.update_game_state(Duration::from_millis(16)).unwrap();
+            server.broadcast_state(tick).unwrap();
+            receiver
+                .try_iter()
+                .map(|bytes| serde_json::from_slice::<ServerMessage>(&bytes).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matches!(
+            sent(&mut server, 0)[..],
+            [ServerMessage::GameState(_)]
+        ));
+        assert!(sent(&mut server, 1).is_empty());
+        assert!(sent(&mut server, 2).is_empty());
+        assert!(matches!(
+            sent(&mut server, 3)[..],
+            [ServerMessage::NoChange { tick: 3 }]
+        ));
+
+        // Once something moves, full snapshots resume every tick
+        server
+            .game_state
+            .players
+            .get_mut("player1")
+            .unwrap()
+            .velocity = Vector2 { x: 5.0, y: 0.0 };
+        for tick in 4..7 {
+            assert!(matches!(
+                sent(&mut server, tick)[..],
+                [ServerMessage::GameState(_)]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_delta_sends_only_changed_fields() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_delta_updates(Some(100));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("observer".to_string(), sender);
+        server.game_state.add_player(
+            "player1".to_string(),
+            PlayerState {
+                velocity: Vector2 { x: 5.0, y: 0.0 },
+                ..Default::default()
+            },
+        );
+
+        server.broadcast_state(0).unwrap();
+        assert!(matches!(
+            next_message(&receiver),
+            ServerMessage::GameState(_)
+        ));
+
+        server.update_game_state(Duration::from_millis(16)).unwrap();
+        server.broadcast_state(1).unwrap();
+
+        let bytes = receiver.try_recv().expect("No delta sent");
+        let json = String::from_utf8(bytes.clone()).unwrap();
         assert!(
-            updated_state.position.x <= rules.map_bounds.1,
-            "Player should not move beyond map bounds"
+            !json.contains("velocity"),
+            "Unchanged velocity sent: {}",
+            json
+        );
+        match serde_json::from_slice(&bytes).unwrap() {
+            ServerMessage::GameStateDelta(delta) => {
+                assert_eq!((delta.base_tick, delta.tick), (0, 1));
+                let player = &delta.changed["player1"];
+                assert!(player.position.is_some());
+                assert!(player.velocity.is_none() && player.full.is_none());
+            }
+            other => panic!("Expected GameStateDelta, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_large_snapshot_split_into_parts() {
+        let mut server = GameServer::new("127.0.0.1:0").unwrap();
+        server.set_max_players_per_update(Some(2));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        server.add_connection("observer".to_string(), sender);
+        for i in 0..5 {
+            server
+                .game_state
+                .add_player(format!("player{}", i), PlayerState::default());
+        }
+
+        server.broadcast_state(3).expect("Broadcast failed");
+
+        let mut ids = Vec::new();
+        let mut parts = Vec::new();
+        while let Ok(bytes) = receiver.try_recv() {
+            match serde_json::from_slice::<ServerMessage>(&bytes).unwrap() {
+                ServerMessage::GameState(update) => {
+                    assert_eq!(update.tick, 3);
+                    assert_eq!(update.of, 3);
+                    assert!(update.players.len() <= 2);
+                    parts.push(update.part);
+                    ids.extend(update.players.into_keys());
+                }
+                other => panic!("Expected GameState, got {:?}", other),
+            }
+        }
+        ids.sort();
+
+        assert_eq!(parts, vec![0, 1, 2]);
+        assert_eq!(
+            ids,
+            (0..5).map(|i| format!("player{}", i)).collect::<Vec<_>>()
         );
     }
 }
@@ -0,0 +1,83 @@
+use crate::common::{PlayerState, Vector2};
+use std::collections::{HashMap, VecDeque};
+
+// Recent positions per player, for rewinding to what a lagging client saw
+#[derive(Clone, Debug)]
+pub struct PositionHistory {
+    capacity: usize,
+    by_player: HashMap<String, VecDeque<(u64, Vector2)>>,
+}
+
+impl PositionHistory {
+    // Keeps the last `capacity` ticks; zero keeps nothing
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            by_player: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, players: &HashMap<String, PlayerState>) {
+        if self.capacity == 0 {
+            return;
+        }
+        for (player_id, state) in players {
+            let history = self.by_player.entry(player_id.clone()).or_default();
+            if history.len() == self.capacity {
+                history.pop_front();
+            }
+            history.push_back((tick, state.position));
+        }
+    }
+
+    pub fn remove(&mut self, player_id: &str) {
+        self.by_player.remove(player_id);
+    }
+
+    // Keeps a player's history when they continue under a new id
+    pub fn transfer(&mut self, from: &str, to: &str) {
+        if let Some(history) = self.by_player.remove(from) {
+            self.by_player.insert(to.to_string(), history);
+        }
+    }
+
+    // The player's position at `tick`, or the latest recorded before it. Ticks outside
+    // the history are clamped to the oldest or newest entry. Returns the tick used
+    pub fn rewind(&self, player_id: &str, tick: u64) -> Option<(u64, Vector2)> {
+        let history = self.by_player.get(player_id)?;
+        let index = history.partition_point(|&(recorded, _)| recorded <= tick);
+        history.get(index.saturating_sub(1)).copied()
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32) -> HashMap<String, PlayerState> {
+        HashMap::from([(
+            "player1".to_string(),
+            PlayerState {
+                position: Vector2 { x, y: 0.0 },
+                ..Default::default()
+            },
+        )])
+    }
+
+    #[test]
+    fn test_rewind_clamped_to_history() {
+        let mut history = PositionHistory::new(3);
+        for tick in 1..=5 {
+            history.record(tick, &at(tick as f32));
+        }
+
+        // Ticks 1 and 2 have been dropped
+        let (tick, position) = history.rewind("player1", 1).unwrap();
+        assert_eq!((tick, position.x), (3, 3.0));
+
+        assert_eq!(history.rewind("player1", 4).unwrap().0, 4);
+        assert_eq!(history.rewind("player1", 99).unwrap().0, 5);
+        assert!(history.rewind("missing", 4).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::common::Vector2;
+use std::fmt::Debug;
+
+// Advances a player's position over one tick
+pub trait Integrator: Debug + Send + Sync {
+    fn integrate(
+        &self,
+        position: Vector2,
+        velocity: Vector2,
+        previous_velocity: Vector2,
+        delta_time: f32,
+    ) -> Vector2;
+}
+
+// Moves by the current velocity for the whole tick
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EulerIntegrator;
+
+impl Integrator for EulerIntegrator {
+    fn integrate(
+        &self,
+        position: Vector2,
+        velocity: Vector2,
+        _previous_velocity: Vector2,
+        delta_time: f32,
+    ) -> Vector2 {
+        position.add(&velocity.scale(delta_time))
+    }
+}
+
+// Velocity Verlet: treats a velocity change as constant acceleration over the
+// tick, so the player moves by the average of the previous and current velocity.
+// Matches Euler while velocity is constant, but eases into velocity changes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VerletIntegrator;
+
+impl Integrator for VerletIntegrator {
+    fn integrate(
+        &self,
+        position: Vector2,
+        velocity: Vector2,
+        previous_velocity: Vector2,
+        delta_time: f32,
+    ) -> Vector2 {
+        let average_velocity = velocity.add(&previous_velocity).scale(0.5);
+        position.add(&average_velocity.scale(delta_time))
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STILL: Vector2 = Vector2 { x: 0.0, y: 0.0 };
+    const RIGHT: Vector2 = Vector2 { x: 10.0, y: 0.0 };
+
+    // Starts still, then moves right for three ticks
+    fn trajectory(integrator: &dyn Integrator) -> Vec<f32> {
+        let mut position = STILL;
+        let mut previous_velocity = STILL;
+        let mut xs = Vec::new();
+
+        for _ in 0..3 {
+            position = integrator.integrate(position, RIGHT, previous_velocity, 0.1);
+            previous_velocity = RIGHT;
+            xs.push(position.x);
+        }
+        xs
+    }
+
+    #[test]
+    fn test_euler_matches_velocity_times_delta() {
+        let xs = trajectory(&EulerIntegrator);
+        assert_eq!(xs, vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_verlet_eases_into_velocity_change() {
+        let xs = trajectory(&VerletIntegrator);
+
+        // Half a step on the tick velocity changes, then full steps like Euler
+        assert_eq!(xs, vec![0.5, 1.5, 2.5]);
+    }
+}
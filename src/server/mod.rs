@@ -1,4 +1,24 @@
+mod bots;
+mod broadcaster;
+mod events;
 mod game;
+mod history;
+mod integrator;
 mod network;
+mod projection;
+mod rate_limit;
+mod slots;
+mod stats;
 
-pub use game::GameServer;
+pub use bots::BotBehavior;
+pub use events::{Event, EventKind, EventLog};
+pub use game::{
+    BoundaryMode, DuplicatePolicy, GameRules, GameServer, GameServerBuilder, GameState,
+    JoinRequest, JoinValidator, NamePolicy, OverflowPolicy, SpawnStrategy, TickHook,
+};
+pub use history::PositionHistory;
+pub use integrator::{EulerIntegrator, Integrator, VerletIntegrator};
+pub use projection::SnapshotField;
+pub use rate_limit::TokenBucket;
+pub use slots::SlotTable;
+pub use stats::{MessageSizeHistogram, ServerStats, MESSAGE_SIZE_BUCKETS};
@@ -1,20 +1,169 @@
-use crate::common::{ClientMessage, GameError, InternalMessage, Result, ServerMessage, Vector2};
+use crate::common::{ClientMessage, ErrorCode, GameError, InternalMessage, Result, ServerMessage};
 use std::{
     io::{BufRead, Write},
-    sync::mpsc::{channel, Receiver, Sender},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
-pub struct PlayerConnection {
-    pub player_id: String,
-    pub sender: Sender<Vec<u8>>,
+
+// Per-connection socket options
+#[derive(Clone, Debug)]
+pub struct NetworkConfig {
+    // How long a write may block before the player is dropped as lagging
+    pub write_timeout: Option<Duration>,
+    // Connections beyond this many are refused, each costs two threads
+    pub max_connections: usize,
+    // Connections that send no bytes at all for this long are dropped
+    pub read_deadline: Option<Duration>,
+    // Test seams: runs before each socket clone, and replaces the line parser,
+    // so tests can simulate clone failures and reader panics
+    #[cfg(test)]
+    pub(crate) before_split: Option<fn() -> std::io::Result<()>>,
+    #[cfg(test)]
+    pub(crate) parse_hook: Option<fn(&str) -> serde_json::Result<ClientMessage>>,
+}
+
+// How often a blocked reader wakes to check the read deadline
+const READ_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            write_timeout: Some(Duration::from_secs(5)),
+            max_connections: 256,
+            read_deadline: None,
+            #[cfg(test)]
+            before_split: None,
+            #[cfg(test)]
+            parse_hook: None,
+        }
+    }
+}
+
+// Counts a live connection until dropped by its reader thread
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Reports the player as disconnected when the reader thread exits for any
+// reason, including errors and panics, so the game state never leaks them
+pub struct DisconnectGuard {
+    player_id: String,
+    message_sender: Sender<InternalMessage>,
+}
+
+impl DisconnectGuard {
+    pub fn new(player_id: String, message_sender: Sender<InternalMessage>) -> Self {
+        Self {
+            player_id,
+            message_sender,
+        }
+    }
+}
+
+impl Drop for DisconnectGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("Reader thread for {} panicked", self.player_id);
+        }
+        let _ = self.message_sender.send(InternalMessage::ClientMessage {
+            player_id: self.player_id.clone(),
+            message: ClientMessage::Disconnect,
+        });
+    }
+}
+
+struct ShutdownOnDrop(TcpStream);
+
+impl Drop for ShutdownOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown(Shutdown::Both);
+    }
+}
+
+fn reject_connection(mut stream: TcpStream, reason: &str) {
+    let rejection = ServerMessage::Error {
+        error_code: ErrorCode::ServerFull,
+        message: reason.to_string(),
+    };
+    if let Ok(json) = serde_json::to_string(&rejection) {
+        let _ = stream.write_all(format!("{}\n", json).as_bytes());
+    }
+    let _ = stream.shutdown(Shutdown::Both);
 }
 
-pub fn handle_connections(address: &str, message_sender: Sender<InternalMessage>) -> Result<()> {
-    let listener = std::net::TcpListener::bind(address)?;
-    println!("Listening for connections on {}", address);
+// Accepts on every listener into the same game, one thread per listener.
+// max_connections counts across all of them
+pub fn serve(
+    listeners: Vec<TcpListener>,
+    message_sender: Sender<InternalMessage>,
+    config: NetworkConfig,
+) {
+    let active_connections = Arc::new(AtomicUsize::new(0));
+    let accept_loops: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| {
+            let message_sender = message_sender.clone();
+            let config = config.clone();
+            let active_connections = active_connections.clone();
+            std::thread::spawn(move || {
+                accept_connections(listener, message_sender, config, active_connections)
+            })
+        })
+        .collect();
+
+    // One listener failing leaves the others serving
+    for accept_loop in accept_loops {
+        match accept_loop.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Network error: {}", e),
+            Err(_) => eprintln!("Accept thread panicked"),
+        }
+    }
+}
+
+// Clones the socket into the writer thread's half and a handle for shutting it down
+fn split_stream(
+    stream: &TcpStream,
+    config: &NetworkConfig,
+) -> std::io::Result<(TcpStream, TcpStream)> {
+    #[cfg(test)]
+    if let Some(before_split) = config.before_split {
+        before_split()?;
+    }
+    let write_stream = stream.try_clone()?;
+    write_stream.set_write_timeout(config.write_timeout)?;
+    Ok((write_stream, stream.try_clone()?))
+}
 
+fn accept_connections(
+    listener: TcpListener,
+    message_sender: Sender<InternalMessage>,
+    config: NetworkConfig,
+    active_connections: Arc<AtomicUsize>,
+) -> Result<()> {
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
+                if active_connections.load(Ordering::SeqCst) >= config.max_connections {
+                    eprintln!(
+                        "Refusing connection: {} already open",
+                        config.max_connections
+                    );
+                    reject_connection(stream, "Too many connections");
+                    continue;
+                }
+                active_connections.fetch_add(1, Ordering::SeqCst);
+                let guard = ConnectionGuard(active_connections.clone());
+
                 let message_sender = message_sender.clone();
 
                 let player_id = stream
@@ -22,6 +171,15 @@ pub fn handle_connections(address: &str, message_sender: Sender<InternalMessage>
                     .map(|addr| addr.to_string())
                     .unwrap_or_else(|_| "unknown".to_string());
 
+                // A failure here only costs this connection, not the accept loop
+                let (write_stream, shutdown_stream) = match split_stream(&stream, &config) {
+                    Ok(streams) => streams,
+                    Err(e) => {
+                        eprintln!("Dropping connection from {}: {}", player_id, e);
+                        continue;
+                    }
+                };
+
                 // Create message channel for this client
                 let (client_sender, client_receiver) = channel();
 
@@ -33,24 +191,43 @@ pub fn handle_connections(address: &str, message_sender: Sender<InternalMessage>
                     })
                     .map_err(|_| GameError::NetworkError("Failed to register connection".into()))?;
 
-                // Clone stream for writer thread
-                let write_stream = stream.try_clone()?;
-
                 // Spawn writer thread
+                let writer_player_id = player_id.clone();
                 std::thread::spawn(move || {
-                    if let Err(e) = handle_client_writer(write_stream, client_receiver) {
-                        eprintln!("Writer thread error: {}", e);
+                    // Closing the socket on any exit, even a panic, wakes the reader
+                    let _shutdown = ShutdownOnDrop(shutdown_stream);
+                    if let Err(e) =
+                        handle_client_writer(write_stream, &writer_player_id, client_receiver)
+                    {
+                        match e.root() {
+                            GameError::IoError(io)
+                                if matches!(
+                                    io.kind(),
+                                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                                ) =>
+                            {
+                                eprintln!("Player {} is lagging, disconnecting", writer_player_id)
+                            }
+                            _ => eprintln!("Writer thread error: {}", e),
+                        }
                     }
                 });
 
                 // Spawn reader thread
                 let message_sender_clone = message_sender.clone();
                 let player_id_clone = player_id.clone();
+                let reader_config = config.clone();
+                let disconnect = DisconnectGuard::new(player_id.clone(), message_sender.clone());
                 std::thread::spawn(move || {
-                    if let Err(e) =
-                        handle_client_reader(stream, player_id_clone, message_sender_clone)
-                    {
-                        eprintln!("Client error for {}: {}", player_id, e);
+                    let _guard = guard;
+                    let _disconnect = disconnect;
+                    if let Err(e) = handle_client_reader(
+                        stream,
+                        player_id_clone,
+                        message_sender_clone,
+                        &reader_config,
+                    ) {
+                        eprintln!("Client error: {}", e);
                     }
                 });
             }
@@ -61,49 +238,99 @@ pub fn handle_connections(address: &str, message_sender: Sender<InternalMessage>
 }
 
 fn handle_client_reader(
-    stream: std::net::TcpStream,
+    stream: TcpStream,
     player_id: String,
     message_sender: Sender<InternalMessage>,
+    config: &NetworkConfig,
 ) -> Result<()> {
+    read_client_messages(stream, &player_id, message_sender, config)
+        .map_err(|e| e.with_context(format!("player {}", player_id)))
+}
+
+fn read_client_messages(
+    stream: TcpStream,
+    player_id: &str,
+    message_sender: Sender<InternalMessage>,
+    config: &NetworkConfig,
+) -> Result<()> {
+    let read_deadline = config.read_deadline;
+    // Wake periodically so a dead-silent connection can be noticed
+    if let Some(deadline) = read_deadline {
+        stream.set_read_timeout(Some(deadline.min(READ_POLL_INTERVAL)))?;
+    }
     let mut reader = std::io::BufReader::new(stream);
     let mut line = String::new();
+    let mut last_activity = Instant::now();
 
     loop {
-        line.clear();
         match reader.read_line(&mut line) {
             Ok(0) => break, // EOF
-            Ok(_) => match serde_json::from_str::<ClientMessage>(&line) {
-                Ok(message) => {
-                    message_sender
-                        .send(InternalMessage::ClientMessage {
-                            player_id: player_id.clone(),
-                            message,
-                        })
-                        .map_err(|_| GameError::NetworkError("Failed to forward message".into()))?;
+            Ok(_) => {
+                last_activity = Instant::now();
+                match parse_client_message(&line, config) {
+                    Ok(message) => {
+                        message_sender
+                            .send(InternalMessage::ClientMessage {
+                                player_id: player_id.to_string(),
+                                message,
+                            })
+                            .map_err(|_| {
+                                GameError::NetworkError("Failed to forward message".into())
+                            })?;
+                    }
+                    Err(e) => eprintln!("Failed to parse message from {}: {}", player_id, e),
                 }
-                Err(e) => eprintln!("Failed to parse message from {}: {}", player_id, e),
-            },
+                line.clear();
+            }
+            // Timed out waiting; any partial line stays buffered in `line`
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                if !line.is_empty() {
+                    last_activity = Instant::now();
+                }
+                if read_deadline.is_some_and(|deadline| last_activity.elapsed() >= deadline) {
+                    eprintln!(
+                        "Player {} sent nothing for too long, disconnecting",
+                        player_id
+                    );
+                    let _ = reader.get_ref().shutdown(Shutdown::Both);
+                    break;
+                }
+            }
             Err(e) => {
                 return Err(GameError::IoError(e));
             }
         }
     }
 
-    // Send disconnect message when client disconnects
-    let _ = message_sender.send(InternalMessage::ClientMessage {
-        player_id: player_id.clone(),
-        message: ClientMessage::Disconnect,
-    });
     Ok(())
 }
 
+// `config` only carries the test parse hook
+#[cfg_attr(not(test), allow(unused_variables))]
+fn parse_client_message(line: &str, config: &NetworkConfig) -> serde_json::Result<ClientMessage> {
+    #[cfg(test)]
+    if let Some(parse) = config.parse_hook {
+        return parse(line);
+    }
+    serde_json::from_str(line)
+}
+
 fn handle_client_writer(
-    mut stream: std::net::TcpStream,
+    mut stream: TcpStream,
+    player_id: &str,
     receiver: Receiver<Vec<u8>>,
 ) -> Result<()> {
     for message in receiver {
-        stream.write_all(&message)?;
-        stream.flush()?;
+        if let Err(e) = stream.write_all(&message).and_then(|_| stream.flush()) {
+            // Closing the socket wakes the reader, which reports the disconnect
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(GameError::IoError(e).with_context(format!("player {}", player_id)));
+        }
     }
     Ok(())
 }
@@ -112,7 +339,7 @@ fn handle_client_writer(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::net::{TcpListener, TcpStream};
+    use crate::common::{ServerMessage, Vector2};
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
     use std::thread;
@@ -135,24 +362,12 @@ mod tests {
             self.stream.flush()?;
             Ok(())
         }
-
-        fn receive_message(&mut self) -> Result<Option<ServerMessage>> {
-            let mut reader = std::io::BufReader::new(&self.stream);
-            let mut line = String::new();
-            match reader.read_line(&mut line) {
-                Ok(0) => Ok(None),
-                Ok(_) => Ok(Some(serde_json::from_str(&line)?)),
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
-                Err(e) => Err(GameError::IoError(e)),
-            }
-        }
     }
 
     struct TestServer {
         address: String,
         shutdown: Arc<AtomicBool>,
         handle: Option<thread::JoinHandle<()>>,
-        message_sender: std::sync::mpsc::Sender<InternalMessage>,
     }
 
     impl TestServer {
@@ -166,8 +381,7 @@ mod tests {
                 .expect("Failed to get local address")
                 .to_string();
 
-            let (tx, _rx) = std::sync::mpsc::channel();
-            let message_sender = tx.clone();
+            let (message_sender, _rx) = std::sync::mpsc::channel();
             let shutdown = Arc::new(AtomicBool::new(false));
             let shutdown_flag = shutdown.clone();
 
@@ -194,6 +408,7 @@ mod tests {
                                     stream,
                                     player_id.clone(),
                                     message_sender_clone,
+                                    &NetworkConfig::default(),
                                 ) {
                                     eprintln!("Test client error: {}", e);
                                 }
@@ -212,7 +427,6 @@ mod tests {
                 address: server_addr,
                 shutdown,
                 handle: Some(handle),
-                message_sender: tx,
             }
         }
     }
@@ -250,7 +464,10 @@ mod tests {
 
         // Send Join message
         client
-            .send_message(&ClientMessage::Join)
+            .send_message(&ClientMessage::Join {
+                name: None,
+                token: None,
+            })
             .expect("Failed to send join");
 
         // Wait a bit for server processing
@@ -283,7 +500,10 @@ mod tests {
 
         // Join game
         client
-            .send_message(&ClientMessage::Join)
+            .send_message(&ClientMessage::Join {
+                name: None,
+                token: None,
+            })
             .expect("Failed to send join");
 
         // Wait for processing
@@ -292,6 +512,8 @@ mod tests {
         // Send movement
         let move_msg = ClientMessage::Move {
             direction: Vector2 { x: 1.0, y: 0.0 },
+            client_time: None,
+            throttle: None,
         };
         client
             .send_message(&move_msg)
@@ -302,4 +524,170 @@ mod tests {
 
         // Cleanup happens automatically when server and client are dropped
     }
+
+    #[test]
+    fn test_non_reading_client_dropped_after_write_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = channel();
+        let config = NetworkConfig {
+            write_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        thread::spawn(move || serve(vec![listener], tx, config));
+
+        // Connect but never read from the socket
+        let _client = TcpStream::connect(&address).expect("Failed to connect");
+
+        let (player_id, sender) = match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(InternalMessage::NewConnection { player_id, sender }) => (player_id, sender),
+            _ => panic!("Expected a new connection"),
+        };
+
+        // Queue far more data than the socket buffers can hold
+        let chunk = vec![b'x'; 1024 * 1024];
+        for _ in 0..64 {
+            if sender.send(chunk.clone()).is_err() {
+                break;
+            }
+        }
+
+        match rx.recv_timeout(Duration::from_secs(10)) {
+            Ok(InternalMessage::ClientMessage {
+                player_id: id,
+                message: ClientMessage::Disconnect,
+            }) => assert_eq!(id, player_id),
+            _ => panic!("Lagging client should have been disconnected"),
+        }
+    }
+
+    #[test]
+    fn test_write_error_names_player() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let client = TcpStream::connect(listener.local_addr().unwrap()).expect("Failed to connect");
+        let (stream, _) = listener.accept().expect("Failed to accept");
+        drop(client);
+
+        let (sender, receiver) = channel();
+        let chunk = vec![b'x'; 64 * 1024];
+        for _ in 0..64 {
+            sender.send(chunk.clone()).unwrap();
+        }
+        drop(sender);
+
+        let error = handle_client_writer(stream, "player-7", receiver)
+            .expect_err("Writing to a closed peer should fail");
+        assert!(matches!(error.root(), GameError::IoError(_)));
+        assert!(
+            error.to_string().contains("player-7"),
+            "Missing player id in: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_silent_client_dropped_after_read_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = channel();
+        let config = NetworkConfig {
+            read_deadline: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        thread::spawn(move || serve(vec![listener], tx, config));
+
+        // Connect but never send a byte
+        let _client = TcpStream::connect(&address).expect("Failed to connect");
+
+        let player_id = match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(InternalMessage::NewConnection { player_id, .. }) => player_id,
+            _ => panic!("Expected a new connection"),
+        };
+
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(InternalMessage::ClientMessage {
+                player_id: id,
+                message: ClientMessage::Disconnect,
+            }) => assert_eq!(id, player_id),
+            _ => panic!("Silent client should have been disconnected"),
+        }
+    }
+
+    #[test]
+    fn test_clone_failure_skips_only_that_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = channel();
+        // Only this test uses the counter, so parallel tests don't disturb it
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+        let config = NetworkConfig {
+            before_split: Some(|| {
+                if ATTEMPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(std::io::Error::other("simulated clone failure"));
+                }
+                Ok(())
+            }),
+            ..Default::default()
+        };
+        thread::spawn(move || serve(vec![listener], tx, config));
+
+        let first = TcpStream::connect(&address).expect("Failed to connect");
+        first
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let mut line = String::new();
+        let read = std::io::BufReader::new(&first).read_line(&mut line);
+        assert!(matches!(read, Ok(0)), "Failed connection should be closed");
+
+        // The accept loop is still running
+        let _second = TcpStream::connect(&address).expect("Failed to connect");
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(InternalMessage::NewConnection { .. }) => {}
+            _ => panic!("Second connection should register"),
+        }
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_excess_connections_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = channel();
+        let config = NetworkConfig {
+            max_connections: 2,
+            ..Default::default()
+        };
+        thread::spawn(move || serve(vec![listener], tx, config));
+
+        let clients: Vec<TcpStream> = (0..5)
+            .map(|_| TcpStream::connect(&address).expect("Failed to connect"))
+            .collect();
+
+        // Excess clients are told why and disconnected
+        let mut refused = 0;
+        for client in &clients[2..] {
+            client
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            let mut reader = std::io::BufReader::new(client);
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("Expected a rejection");
+            if let Ok(ServerMessage::Error { error_code, .. }) = serde_json::from_str(&line) {
+                assert_eq!(error_code, ErrorCode::ServerFull);
+                refused += 1;
+            }
+        }
+        assert_eq!(refused, 3);
+
+        let mut registered = 0;
+        while let Ok(message) = rx.recv_timeout(Duration::from_millis(200)) {
+            if let InternalMessage::NewConnection { .. } = message {
+                registered += 1;
+            }
+        }
+        assert_eq!(
+            registered, 2,
+            "Only the first two connections should register"
+        );
+    }
 }
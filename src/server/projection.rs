@@ -0,0 +1,330 @@
+use crate::common::{
+    GameStateDelta, GameStateUpdate, PlayerDelta, PlayerState, PlayerStatus, Timestamp, Vector2,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// PlayerState fields that can be included in broadcast snapshots
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SnapshotField {
+    Position,
+    Velocity,
+    LastUpdate,
+    Mass,
+    Name,
+    Metadata,
+    Status,
+    MaxSpeed,
+    ClientAuthoritative,
+}
+
+// Serializes like ServerMessage::GameState or GameStateDelta, so clients parse it as
+// usual; fields left out of the allowlist fall back to their defaults on the client,
+// and sorted pairs are read back into the players map
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub(super) enum ProjectedMessage<'a> {
+    GameState(ProjectedUpdate<'a>),
+    GameStateDelta(ProjectedDelta<'a>),
+}
+
+#[derive(Serialize)]
+pub(super) struct ProjectedUpdate<'a> {
+    tick: u64,
+    players: Players<'a>,
+    server_time: &'a Timestamp,
+    part: u32,
+    of: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bounds: Option<(Vector2, Vector2)>,
+}
+
+// A map as usual, or (id, state) pairs sorted by id for byte-stable output
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Players<'a> {
+    Map(HashMap<&'a str, PlayerView<'a>>),
+    Sorted(Vec<(&'a str, PlayerView<'a>)>),
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum PlayerView<'a> {
+    Full(&'a PlayerState),
+    Projected(PlayerProjection<'a>),
+}
+
+impl<'a> ProjectedUpdate<'a> {
+    // Trims players to `fields` when given, and sorts them by id when `sorted`
+    pub(super) fn new(
+        update: &'a GameStateUpdate,
+        fields: Option<&HashSet<SnapshotField>>,
+        sorted: bool,
+    ) -> Self {
+        let views = update.players.iter().map(|(id, state)| {
+            let view = match fields {
+                Some(fields) => PlayerView::Projected(PlayerProjection::new(state, fields)),
+                None => PlayerView::Full(state),
+            };
+            (id.as_str(), view)
+        });
+        let players = if sorted {
+            let mut players: Vec<_> = views.collect();
+            players.sort_by_key(|(id, _)| *id);
+            Players::Sorted(players)
+        } else {
+            Players::Map(views.collect())
+        };
+        Self {
+            tick: update.tick,
+            players,
+            server_time: &update.server_time,
+            part: update.part,
+            of: update.of,
+            bounds: update.bounds,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct ProjectedDelta<'a> {
+    tick: u64,
+    base_tick: u64,
+    server_time: &'a Timestamp,
+    changed: HashMap<&'a str, PlayerDeltaProjection<'a>>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    removed: &'a [String],
+}
+
+#[derive(Serialize)]
+struct PlayerDeltaProjection<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<&'a Vector2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    velocity: Option<&'a Vector2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full: Option<PlayerProjection<'a>>,
+}
+
+impl<'a> ProjectedDelta<'a> {
+    // Trims each change to `fields`, leaving out players whose changes were all trimmed
+    pub(super) fn new(delta: &'a GameStateDelta, fields: &HashSet<SnapshotField>) -> Self {
+        let changed = delta
+            .changed
+            .iter()
+            .map(|(id, change)| (id.as_str(), PlayerDeltaProjection::new(change, fields)))
+            .filter(|(_, change)| !change.is_empty())
+            .collect();
+        Self {
+            tick: delta.tick,
+            base_tick: delta.base_tick,
+            server_time: &delta.server_time,
+            changed,
+            removed: &delta.removed,
+        }
+    }
+}
+
+impl<'a> PlayerDeltaProjection<'a> {
+    fn new(change: &'a PlayerDelta, fields: &HashSet<SnapshotField>) -> Self {
+        let has = |field| fields.contains(&field);
+        Self {
+            position: change
+                .position
+                .as_ref()
+                .filter(|_| has(SnapshotField::Position)),
+            velocity: change
+                .velocity
+                .as_ref()
+                .filter(|_| has(SnapshotField::Velocity)),
+            full: change
+                .full
+                .as_ref()
+                .map(|state| PlayerProjection::new(state, fields)),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position.is_none() && self.velocity.is_none() && self.full.is_none()
+    }
+}
+
+#[derive(Serialize)]
+struct PlayerProjection<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<&'a Vector2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    velocity: Option<&'a Vector2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_update: Option<&'a Timestamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mass: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'a PlayerStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_speed: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_authoritative: Option<bool>,
+}
+
+impl<'a> PlayerProjection<'a> {
+    fn new(state: &'a PlayerState, fields: &HashSet<SnapshotField>) -> Self {
+        let has = |field| fields.contains(&field);
+        Self {
+            position: has(SnapshotField::Position).then_some(&state.position),
+            velocity: has(SnapshotField::Velocity).then_some(&state.velocity),
+            last_update: has(SnapshotField::LastUpdate).then_some(&state.last_update),
+            mass: has(SnapshotField::Mass).then_some(state.mass),
+            name: state.name.as_ref().filter(|_| has(SnapshotField::Name)),
+            metadata: has(SnapshotField::Metadata).then_some(&state.metadata),
+            status: has(SnapshotField::Status).then_some(&state.status),
+            max_speed: state.max_speed.filter(|_| has(SnapshotField::MaxSpeed)),
+            client_authoritative: (state.client_authoritative
+                && has(SnapshotField::ClientAuthoritative))
+            .then_some(true),
+        }
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ServerMessage;
+
+    #[test]
+    fn test_positions_only_snapshot_omits_other_fields() {
+        let player = PlayerState {
+            position: Vector2 { x: 3.0, y: 4.0 },
+            velocity: Vector2 { x: 1.0, y: 0.0 },
+            name: Some("Alice".into()),
+            ..Default::default()
+        };
+        let update = GameStateUpdate::new(
+            5,
+            HashMap::from([("p1".to_string(), player)]),
+            Timestamp::default(),
+        );
+        let fields = HashSet::from([SnapshotField::Position]);
+
+        let json = serde_json::to_string(&ProjectedMessage::GameState(ProjectedUpdate::new(
+            &update,
+            Some(&fields),
+            false,
+        )))
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"GameState","tick":5,"players":{"p1":{"position":{"x":3.0,"y":4.0}}},"server_time":0,"part":0,"of":1}"#
+        );
+
+        // Clients still parse it, with defaults for everything left out
+        let ServerMessage::GameState(parsed) = serde_json::from_str(&json).unwrap() else {
+            panic!("Expected a GameState");
+        };
+        let parsed = &parsed.players["p1"];
+        assert_eq!(parsed.position, Vector2 { x: 3.0, y: 4.0 });
+        assert_eq!(parsed.velocity, Vector2::default());
+        assert_eq!(parsed.name, None);
+    }
+
+    #[test]
+    fn test_sorted_players_serialize_identically() {
+        let ids: Vec<String> = (0..20).map(|i| format!("player{}", i)).collect();
+        let keys = ["skin", "class", "team", "score"];
+        // Players and their metadata both built in the given order
+        let update = |reversed: bool| {
+            let mut players = HashMap::new();
+            for i in 0..ids.len() {
+                let id = &ids[if reversed { ids.len() - 1 - i } else { i }];
+                let mut state = PlayerState::default();
+                for j in 0..keys.len() {
+                    let key = keys[if reversed { keys.len() - 1 - j } else { j }];
+                    state.metadata.insert(key.into(), id.clone());
+                }
+                players.insert(id.clone(), state);
+            }
+            GameStateUpdate::new(1, players, Timestamp::default())
+        };
+        let (forward, backward) = (update(false), update(true));
+        let encode = |update: &GameStateUpdate| {
+            serde_json::to_string(&ProjectedMessage::GameState(ProjectedUpdate::new(
+                update, None, true,
+            )))
+            .unwrap()
+        };
+
+        let json = encode(&forward);
+        assert_eq!(json, encode(&backward));
+        assert!(json.contains(r#""players":[["player0",{"#), "{}", json);
+        assert!(
+            json.contains(r#""metadata":{"class":"player0","score":"player0","skin""#),
+            "{}",
+            json
+        );
+
+        let ServerMessage::GameState(parsed) = serde_json::from_str(&json).unwrap() else {
+            panic!("Expected a GameState");
+        };
+        assert_eq!(parsed.players, forward.players);
+    }
+
+    #[test]
+    fn test_delta_trimmed_to_snapshot_fields() {
+        let moved = PlayerDelta {
+            position: Some(Vector2 { x: 1.0, y: 2.0 }),
+            velocity: Some(Vector2 { x: 3.0, y: 0.0 }),
+            full: None,
+        };
+        let only_velocity = PlayerDelta {
+            position: None,
+            ..moved.clone()
+        };
+        let joined = PlayerDelta {
+            position: None,
+            velocity: None,
+            full: Some(PlayerState {
+                name: Some("Alice".into()),
+                ..Default::default()
+            }),
+        };
+        let delta = GameStateDelta {
+            tick: 8,
+            base_tick: 7,
+            server_time: Timestamp::default(),
+            changed: HashMap::from([
+                ("p1".to_string(), moved),
+                ("p2".to_string(), only_velocity),
+                ("p3".to_string(), joined),
+            ]),
+            removed: vec!["p4".to_string()],
+        };
+        let fields = HashSet::from([SnapshotField::Position]);
+
+        let json = serde_json::to_string(&ProjectedMessage::GameStateDelta(ProjectedDelta::new(
+            &delta, &fields,
+        )))
+        .unwrap();
+        let ServerMessage::GameStateDelta(parsed) = serde_json::from_str(&json).unwrap() else {
+            panic!("Expected a GameStateDelta");
+        };
+        assert_eq!(parsed.removed, vec!["p4".to_string()]);
+        assert_eq!(
+            parsed.changed["p1"],
+            PlayerDelta {
+                position: Some(Vector2 { x: 1.0, y: 2.0 }),
+                velocity: None,
+                full: None,
+            }
+        );
+        // Nothing allowed changed
+        assert!(!parsed.changed.contains_key("p2"));
+        let full = parsed.changed["p3"].full.as_ref().unwrap();
+        assert_eq!(full.name, None);
+        assert_eq!(full.position, Vector2::default());
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::{BTreeSet, HashMap};
+
+// Compact numeric slots for connected players; freed slots are reused lowest first
+#[derive(Clone, Debug, Default)]
+pub struct SlotTable {
+    by_id: HashMap<String, u32>,
+    free: BTreeSet<u32>,
+    next: u32,
+}
+
+impl SlotTable {
+    // Returns the player's slot, assigning one if it has none
+    pub fn assign(&mut self, player_id: &str) -> u32 {
+        if let Some(&slot) = self.by_id.get(player_id) {
+            return slot;
+        }
+        let slot = match self.free.pop_first() {
+            Some(slot) => slot,
+            None => {
+                self.next += 1;
+                self.next - 1
+            }
+        };
+        self.by_id.insert(player_id.to_string(), slot);
+        slot
+    }
+
+    pub fn release(&mut self, player_id: &str) -> Option<u32> {
+        let slot = self.by_id.remove(player_id)?;
+        self.free.insert(slot);
+        Some(slot)
+    }
+
+    // Hands `from`'s slot to `to`, keeping its number
+    pub fn transfer(&mut self, from: &str, to: &str) -> Option<u32> {
+        let slot = self.by_id.remove(from)?;
+        self.by_id.insert(to.to_string(), slot);
+        Some(slot)
+    }
+
+    pub fn get(&self, player_id: &str) -> Option<u32> {
+        self.by_id.get(player_id).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, u32)> {
+        self.by_id.iter().map(|(id, &slot)| (id, slot))
+    }
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_released_slots_reused_lowest_first() {
+        let mut slots = SlotTable::default();
+        assert_eq!(slots.assign("a"), 0);
+        assert_eq!(slots.assign("b"), 1);
+        assert_eq!(slots.assign("c"), 2);
+        assert_eq!(slots.assign("a"), 0, "Assigning twice keeps the slot");
+
+        assert_eq!(slots.release("c"), Some(2));
+        assert_eq!(slots.release("a"), Some(0));
+        assert_eq!(slots.release("a"), None);
+
+        assert_eq!(slots.assign("d"), 0);
+        assert_eq!(slots.assign("e"), 2);
+        assert_eq!(slots.assign("f"), 3);
+        assert_eq!(slots.get("b"), Some(1));
+    }
+}
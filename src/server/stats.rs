@@ -0,0 +1,50 @@
+// Upper bounds (exclusive) of the message size buckets; larger messages land in the last one
+pub const MESSAGE_SIZE_BUCKETS: [usize; 4] = [128, 512, 2048, 8192];
+
+// Counts of outgoing message sizes in bytes: <128, <512, <2k, <8k, >=8k
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageSizeHistogram {
+    counts: [u64; MESSAGE_SIZE_BUCKETS.len() + 1],
+}
+
+impl MessageSizeHistogram {
+    pub fn record(&mut self, size: usize) {
+        let bucket = MESSAGE_SIZE_BUCKETS
+            .iter()
+            .position(|&limit| size < limit)
+            .unwrap_or(MESSAGE_SIZE_BUCKETS.len());
+        self.counts[bucket] += 1;
+    }
+
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+// Counters collected while the server runs
+#[derive(Clone, Debug, Default)]
+pub struct ServerStats {
+    // One entry per serialized broadcast, not per recipient
+    pub message_sizes: MessageSizeHistogram,
+}
+
+// ----------- TESTS ---------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_sizes_bucketed() {
+        let mut histogram = MessageSizeHistogram::default();
+        for size in [0, 127, 128, 511, 2047, 2048, 8191, 8192, 100_000] {
+            histogram.record(size);
+        }
+
+        assert_eq!(histogram.counts(), &[2, 2, 1, 2, 2]);
+        assert_eq!(histogram.total(), 9);
+    }
+}
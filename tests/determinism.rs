@@ -0,0 +1,105 @@
+use networking_basic::common::{ClientMessage, Vector2};
+use networking_basic::server::{GameRules, GameServer, SpawnStrategy};
+use std::time::Duration;
+
+const TICK: Duration = Duration::from_millis(16);
+
+// Runs identically configured servers side by side, feeding both the same inputs,
+// and panics on the first tick where their worlds differ
+struct DeterminismHarness {
+    servers: [GameServer; 2],
+}
+
+impl DeterminismHarness {
+    fn new(rules: GameRules) -> Self {
+        let build = || GameServer::with_rules("127.0.0.1:0", rules.clone()).unwrap();
+        Self {
+            servers: [build(), build()],
+        }
+    }
+
+    fn inject(&mut self, player_id: &str, message: ClientMessage) {
+        for server in &mut self.servers {
+            server.inject(player_id, message.clone()).unwrap();
+        }
+    }
+
+    fn step(&mut self) {
+        for server in &mut self.servers {
+            server.step(TICK).unwrap();
+        }
+        self.assert_in_sync();
+    }
+
+    fn assert_in_sync(&self) {
+        let [a, b] = &self.servers;
+        let tick = a.current_tick();
+        let (a, b) = (a.game_state().players(), b.game_state().players());
+        assert_eq!(a.len(), b.len(), "Player count diverged at tick {}", tick);
+        for (id, state) in a {
+            let other = b
+                .get(id)
+                .unwrap_or_else(|| panic!("{} missing from second server at tick {}", id, tick));
+            assert!(
+                state.visible_eq(other),
+                "{} diverged at tick {}: {:?} vs {:?}",
+                id,
+                tick,
+                state,
+                other
+            );
+        }
+    }
+}
+
+#[test]
+fn test_seeded_servers_stay_in_lockstep() {
+    let rules = GameRules {
+        rng_seed: Some(7),
+        spawn_strategy: SpawnStrategy::Random,
+        spawn_grace: Duration::ZERO,
+        ..Default::default()
+    };
+    let mut harness = DeterminismHarness::new(rules);
+
+    for id in ["left", "right"] {
+        harness.inject(
+            id,
+            ClientMessage::Join {
+                name: None,
+                token: None,
+            },
+        );
+    }
+    harness.assert_in_sync();
+
+    // Steer the players at each other until they collide
+    let mut collided = false;
+    for tick in 0..1200 {
+        if tick % 10 == 0 {
+            for (id, other) in [("left", "right"), ("right", "left")] {
+                let state = harness.servers[0].game_state();
+                let from = state.get_player_state(id).unwrap().position;
+                let to = state.get_player_state(other).unwrap().position;
+                let direction = Vector2 {
+                    x: to.x - from.x,
+                    y: to.y - from.y,
+                }
+                .normalize()
+                .scale(10.0);
+                harness.inject(
+                    id,
+                    ClientMessage::Move {
+                        direction,
+                        client_time: None,
+                        throttle: None,
+                    },
+                );
+            }
+        }
+        harness.step();
+        collided |= !harness.servers[0].game_state().last_collisions().is_empty();
+    }
+
+    assert!(collided, "Inputs should drive the players into a collision");
+}